@@ -157,7 +157,7 @@ fn put_and_iterate_over_prefixes<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>
     let check = |range: std::ops::RangeInclusive<usize>, prefix: Data| {
         let dbtx = store.transaction_ro().unwrap();
         let vals: Vec<_> =
-            dbtx.prefix_iter(MAPID.0, prefix.clone()).unwrap().map(|x| x.1).collect();
+            dbtx.prefix_iter(MAPID.0, prefix.clone()).unwrap().map(|x| owned(x).1).collect();
         let expected: Vec<_> = range.map(|x| Data::from(x.to_string())).collect();
         assert_eq!(vals, expected, "prefix={prefix:?}");
         drop(dbtx);
@@ -171,13 +171,303 @@ fn put_and_iterate_over_prefixes<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>
     check(9..=9, b"x".to_vec());
 }
 
+fn put_and_iterate_interleaved<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for key in [b"a1", b"a2", b"a3", b"b1", b"b2"] {
+        dbtx.put(MAPID.0, key.to_vec(), key.to_vec()).unwrap();
+    }
+    dbtx.commit().expect("commit to succeed");
+
+    // Other reads in the same transaction must not disturb an iteration in progress
+    let dbtx = store.transaction_ro().unwrap();
+    let mut iter_a = dbtx.prefix_iter(MAPID.0, b"a".to_vec()).unwrap().map(owned);
+    assert_eq!(iter_a.next().map(|x| x.0), Some(b"a1".to_vec()));
+
+    let mut iter_b = dbtx.prefix_iter(MAPID.0, b"b".to_vec()).unwrap().map(owned);
+    assert_eq!(iter_b.next().map(|x| x.0), Some(b"b1".to_vec()));
+    assert_eq!(
+        dbtx.get(MAPID.0, b"a3").unwrap().as_deref(),
+        Some(&b"a3"[..])
+    );
+    assert_eq!(iter_a.next().map(|x| x.0), Some(b"a2".to_vec()));
+
+    let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+    assert_eq!(cursor.last().unwrap().map(|x| x.0), Some(b"b2".to_vec()));
+    assert_eq!(iter_b.next().map(|x| x.0), Some(b"b2".to_vec()));
+    assert_eq!(iter_b.next(), None);
+    assert_eq!(iter_a.next().map(|x| x.0), Some(b"a3".to_vec()));
+    assert_eq!(iter_a.next(), None);
+}
+
+fn put_and_iterate_over_ranges<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    // Populate the database with some values
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"ac".to_vec(), b"2".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"bf".to_vec(), b"7".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"ab".to_vec(), b"1".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"aca".to_vec(), b"3".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"bz".to_vec(), b"8".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"x".to_vec(), b"9".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"bb".to_vec(), b"6".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"b".to_vec(), b"5".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"acb".to_vec(), b"4".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"aa".to_vec(), b"0".to_vec()).unwrap();
+    dbtx.commit().expect("commit to succeed");
+
+    fn to_owned(bound: Bound<&[u8]>) -> Bound<Data> {
+        match bound {
+            Included(key) => Included(key.to_vec()),
+            Excluded(key) => Excluded(key.to_vec()),
+            Unbounded => Unbounded,
+        }
+    }
+
+    // Check for items that are supposed to be present
+    // The expected values are given as a string of single-digit values
+    let check = |expected: &str, start: Bound<&[u8]>, end: Bound<&[u8]>| {
+        let dbtx = store.transaction_ro().unwrap();
+        let (start, end) = (to_owned(start), to_owned(end));
+        let vals: Vec<_> = dbtx
+            .range_iter(MAPID.0, start.clone(), end.clone())
+            .unwrap()
+            .map(|x| owned(x).1)
+            .collect();
+        let vals_rev: Vec<_> = dbtx
+            .range_iter_rev(MAPID.0, start.clone(), end.clone())
+            .unwrap()
+            .map(|x| owned(x).1)
+            .collect();
+        let expected: Vec<_> = expected.bytes().map(|x| vec![x]).collect();
+        assert_eq!(vals, expected, "start={start:?}, end={end:?}");
+        let expected_rev: Vec<_> = expected.into_iter().rev().collect();
+        assert_eq!(vals_rev, expected_rev, "start={start:?}, end={end:?} (rev)");
+        drop(dbtx);
+    };
+
+    check("0123456789", Unbounded, Unbounded);
+    check("0123456789", Included(b""), Unbounded);
+    check("01234", Unbounded, Excluded(b"b"));
+    check("012345", Unbounded, Included(b"b"));
+    check("2345", Included(b"ac"), Included(b"b"));
+    check("345", Excluded(b"ac"), Included(b"b"));
+    check("34", Excluded(b"ac"), Excluded(b"b"));
+    check("5678", Included(b"az"), Excluded(b"c"));
+    check("9", Excluded(b"bz"), Unbounded);
+    check("9", Included(b"x"), Included(b"x"));
+    check("", Included(b"x"), Excluded(b"x"));
+    check("", Excluded(b"x"), Excluded(b"x"));
+    check("", Included(b"y"), Unbounded);
+    check("", Included(b"bb"), Included(b"b"));
+    check("", Unbounded, Excluded(b""));
+    check("", Unbounded, Included(b""));
+}
+
+fn put_and_iterate_rev_over_ff_prefixes<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for key in [&[0x00][..], &[0x01, 0xff], &[0x01, 0xff, 0x00], &[0x02], &[0xff], &[0xff, 0xff]] {
+        dbtx.put(MAPID.0, key.to_vec(), key.to_vec()).unwrap();
+    }
+    dbtx.commit().expect("commit to succeed");
+
+    // Prefixes ending with 0xff bytes require special handling of the upper bound
+    let check = |prefix: &[u8], expected: &[&[u8]]| {
+        let dbtx = store.transaction_ro().unwrap();
+        let keys: Vec<_> = dbtx
+            .prefix_iter_rev(MAPID.0, prefix.to_vec())
+            .unwrap()
+            .map(|x| owned(x).0)
+            .collect();
+        let expected: Vec<_> = expected.iter().map(|k| k.to_vec()).collect();
+        assert_eq!(keys, expected, "prefix={prefix:?}");
+    };
+
+    check(&[0x01, 0xff], &[&[0x01, 0xff, 0x00], &[0x01, 0xff]]);
+    check(&[0x01], &[&[0x01, 0xff, 0x00], &[0x01, 0xff]]);
+    check(&[0xff], &[&[0xff, 0xff], &[0xff]]);
+    check(&[0xff, 0xff], &[&[0xff, 0xff]]);
+    check(&[0x03], &[]);
+    check(
+        &[],
+        &[&[0xff, 0xff], &[0xff], &[0x02], &[0x01, 0xff, 0x00], &[0x01, 0xff], &[0x00]],
+    );
+}
+
+fn put_and_move_cursor<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    fn entry(key: &str, val: &str) -> Option<(Data, Data)> {
+        Some((key.into(), val.into()))
+    }
+
+    // Populate the database
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"a".to_vec(), b"0".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"c".to_vec(), b"2".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"e".to_vec(), b"4".to_vec()).unwrap();
+    dbtx.commit().expect("commit to succeed");
+
+    // Move around the committed entries
+    let dbtx = store.transaction_ro().unwrap();
+    let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+    assert_eq!(cursor.current(), Ok(None));
+    assert_eq!(cursor.next(), Ok(entry("a", "0")));
+    assert_eq!(cursor.next(), Ok(entry("c", "2")));
+    assert_eq!(cursor.current(), Ok(entry("c", "2")));
+    assert_eq!(cursor.prev(), Ok(entry("a", "0")));
+    assert_eq!(cursor.prev(), Ok(None));
+    assert_eq!(cursor.current(), Ok(None));
+    assert_eq!(cursor.prev(), Ok(entry("e", "4")));
+    assert_eq!(cursor.next(), Ok(None));
+    assert_eq!(cursor.last(), Ok(entry("e", "4")));
+    assert_eq!(cursor.first(), Ok(entry("a", "0")));
+    assert_eq!(cursor.seek(b"b"), Ok(entry("c", "2")));
+    assert_eq!(cursor.seek(b"c"), Ok(entry("c", "2")));
+    assert_eq!(cursor.seek(b"f"), Ok(None));
+    assert_eq!(cursor.seek(b""), Ok(entry("a", "0")));
+    drop(cursor);
+    drop(dbtx);
+
+    // Move around with uncommitted changes on top
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"b".to_vec(), b"1".to_vec()).unwrap();
+    dbtx.del(MAPID.0, b"c").unwrap();
+    dbtx.put(MAPID.0, b"e".to_vec(), b"5".to_vec()).unwrap();
+    let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+    assert_eq!(cursor.first(), Ok(entry("a", "0")));
+    assert_eq!(cursor.next(), Ok(entry("b", "1")));
+    assert_eq!(cursor.next(), Ok(entry("e", "5")));
+    assert_eq!(cursor.next(), Ok(None));
+    assert_eq!(cursor.last(), Ok(entry("e", "5")));
+    assert_eq!(cursor.prev(), Ok(entry("b", "1")));
+    assert_eq!(cursor.seek(b"c"), Ok(entry("e", "5")));
+    assert_eq!(cursor.prev(), Ok(entry("b", "1")));
+    assert_eq!(cursor.current(), Ok(entry("b", "1")));
+}
+
+fn put_conditionally<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    assert_eq!(
+        dbtx.put_if_absent(MAPID.0, b"a".to_vec(), b"0".to_vec()),
+        Ok(true)
+    );
+    assert_eq!(
+        dbtx.put_if_absent(MAPID.0, b"a".to_vec(), b"1".to_vec()),
+        Ok(false)
+    );
+    dbtx.commit().expect("commit to succeed");
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    assert_eq!(
+        dbtx.put_if_absent(MAPID.0, b"a".to_vec(), b"1".to_vec()),
+        Ok(false)
+    );
+    assert_eq!(dbtx.get(MAPID.0, b"a").unwrap().as_deref(), Some(&b"0"[..]));
+
+    // Swap only succeeds if the expected value matches
+    fn cas<Tx: WriteOps>(
+        dbtx: &mut Tx,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> storage_core::Result<bool> {
+        dbtx.compare_and_swap(MAPID.0, key.to_vec(), expected, new.map(|v| v.to_vec()))
+    }
+    assert_eq!(cas(&mut dbtx, b"a", Some(b"1"), Some(b"2")), Ok(false));
+    assert_eq!(cas(&mut dbtx, b"a", None, Some(b"2")), Ok(false));
+    assert_eq!(cas(&mut dbtx, b"a", Some(b"0"), Some(b"2")), Ok(true));
+    assert_eq!(dbtx.get(MAPID.0, b"a").unwrap().as_deref(), Some(&b"2"[..]));
+
+    // Swapping with None in place of a value checks for absence or deletes
+    assert_eq!(cas(&mut dbtx, b"b", Some(b"0"), Some(b"1")), Ok(false));
+    assert_eq!(cas(&mut dbtx, b"b", None, None), Ok(true));
+    assert_eq!(cas(&mut dbtx, b"b", None, Some(b"1")), Ok(true));
+    assert_eq!(cas(&mut dbtx, b"b", None, None), Ok(false));
+    assert_eq!(cas(&mut dbtx, b"a", Some(b"0"), None), Ok(false));
+    assert_eq!(cas(&mut dbtx, b"a", Some(b"2"), None), Ok(true));
+    assert_eq!(dbtx.get(MAPID.0, b"a"), Ok(None));
+    dbtx.commit().expect("commit to succeed");
+
+    let dbtx = store.transaction_ro().unwrap();
+    let entries: Vec<_> = dbtx.prefix_iter(MAPID.0, Data::new()).unwrap().map(owned).collect();
+    assert_eq!(entries, [(b"b".to_vec(), b"1".to_vec())]);
+}
+
+fn put_and_roll_back_to_savepoints<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    use storage_core::error::Fatal;
+
+    let store = backend_fn().open(desc(1)).expect("db open to succeed");
+
+    fn get<Tx: ReadOps>(dbtx: &Tx, key: &[u8]) -> Option<Data> {
+        dbtx.get(MAPID.0, key).unwrap().map(|v| v.into_owned())
+    }
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    assert_eq!(dbtx.rollback_to(), Err(Fatal::NoSavepoint.into()));
+    assert_eq!(dbtx.release(), Err(Fatal::NoSavepoint.into()));
+    dbtx.put(MAPID.0, b"a".to_vec(), b"0".to_vec()).unwrap();
+
+    // Changes since a savepoint are discarded on rollback
+    dbtx.savepoint().unwrap();
+    dbtx.put(MAPID.0, b"a".to_vec(), b"1".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"b".to_vec(), b"1".to_vec()).unwrap();
+    dbtx.rollback_to().unwrap();
+    assert_eq!(get(&dbtx, b"a"), Some(b"0".to_vec()));
+    assert_eq!(get(&dbtx, b"b"), None);
+
+    // Nested savepoints, inner one released into the outer one which is then rolled back
+    dbtx.savepoint().unwrap();
+    dbtx.del(MAPID.0, b"a").unwrap();
+    dbtx.savepoint().unwrap();
+    dbtx.put(MAPID.0, b"c".to_vec(), b"2".to_vec()).unwrap();
+    dbtx.release().unwrap();
+    assert_eq!(get(&dbtx, b"a"), None);
+    assert_eq!(get(&dbtx, b"c"), Some(b"2".to_vec()));
+    dbtx.rollback_to().unwrap();
+    assert_eq!(get(&dbtx, b"a"), Some(b"0".to_vec()));
+    assert_eq!(get(&dbtx, b"c"), None);
+
+    // Nested savepoints, inner one rolled back and the outer one kept active until commit
+    dbtx.savepoint().unwrap();
+    dbtx.put(MAPID.0, b"d".to_vec(), b"3".to_vec()).unwrap();
+    dbtx.savepoint().unwrap();
+    dbtx.put(MAPID.0, b"d".to_vec(), b"4".to_vec()).unwrap();
+    dbtx.put(MAPID.0, b"e".to_vec(), b"4".to_vec()).unwrap();
+    dbtx.rollback_to().unwrap();
+    assert_eq!(get(&dbtx, b"d"), Some(b"3".to_vec()));
+    assert_eq!(get(&dbtx, b"e"), None);
+    dbtx.commit().expect("commit to succeed");
+
+    let dbtx = store.transaction_ro().unwrap();
+    let entries: Vec<_> = dbtx.prefix_iter(MAPID.0, Data::new()).unwrap().map(owned).collect();
+    let expected = [(b"a".to_vec(), b"0".to_vec()), (b"d".to_vec(), b"3".to_vec())];
+    assert_eq!(entries, expected);
+}
+
 // Check for items that are supposed to be present
 fn check_prefix<Tx: ReadOps>(dbtx: &Tx, prefix: Data, expected: &[(&str, &str)]) {
-    let entries = dbtx.prefix_iter(MAPID.0, prefix).unwrap();
+    let entries = dbtx.prefix_iter(MAPID.0, prefix.clone()).unwrap().map(owned);
     let expected = expected
         .iter()
         .map(|(x, y)| (Data::from(x.to_string()), Data::from(y.to_string())));
-    assert!(entries.eq(expected));
+    assert!(entries.eq(expected.clone()));
+
+    // Iterating over the keys only should give the same keys
+    let keys = dbtx.prefix_iter_keys(MAPID.0, prefix.clone()).unwrap().map(owned_key);
+    assert!(keys.eq(expected.clone().map(|(k, _)| k)));
+
+    // The same entries should come out in reverse when iterating in descending order
+    let entries_rev = dbtx.prefix_iter_rev(MAPID.0, prefix).unwrap().map(owned);
+    assert!(entries_rev.eq(expected.rev()));
 }
 
 fn put_and_iterate_delete_some<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
@@ -250,11 +540,103 @@ fn put_and_iterate_delete_some<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>)
     drop(dbtx);
 }
 
+fn put_into_maps_with_odd_names<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    // Names that are not valid identifiers in most query languages
+    let names = ["select", "with space", "quo\"te", "it's", "semi;colon--", "[bracket]"];
+    let desc = storage_core::types::construct::db_desc(names.into_iter().map(DbMapDesc::new));
+    let store = backend_fn().open(desc).expect("db open to succeed");
+    let map_ids = || (0..names.len()).map(DbMapId::new);
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for (map_id, name) in map_ids().zip(names) {
+        dbtx.put(map_id, b"key".to_vec(), name.into()).unwrap();
+    }
+    dbtx.commit().expect("commit to succeed");
+
+    // Each map holds its own value
+    let dbtx = store.transaction_ro().unwrap();
+    for (map_id, name) in map_ids().zip(names) {
+        let entries: Vec<_> = dbtx.prefix_iter(map_id, Data::new()).unwrap().map(owned).collect();
+        assert_eq!(entries, [(b"key".to_vec(), Data::from(name))]);
+    }
+    drop(dbtx);
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.clear(DbMapId::new(0)).unwrap();
+    dbtx.del(DbMapId::new(1), b"key").unwrap();
+    dbtx.commit().expect("commit to succeed");
+
+    let dbtx = store.transaction_ro().unwrap();
+    assert_eq!(dbtx.get(DbMapId::new(0), b"key"), Ok(None));
+    assert_eq!(dbtx.get(DbMapId::new(1), b"key"), Ok(None));
+    assert_eq!(
+        dbtx.get(DbMapId::new(2), b"key").unwrap().as_deref(),
+        Some(names[2].as_bytes())
+    );
+}
+
+fn put_and_delete_ranges<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    use std::ops::Bound;
+
+    let store = backend_fn().open(desc(2)).expect("db open to succeed");
+
+    let entries = [("aa", "0"), ("ab", "1"), ("ac", "2"), ("aca", "3"), ("acb", "4"), ("b", "5")];
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for (key, val) in entries {
+        dbtx.put(MAPID.0, key.into(), val.into()).unwrap();
+        dbtx.put(MAPID.1, key.into(), val.into()).unwrap();
+    }
+    dbtx.commit().expect("commit to succeed");
+
+    // Delete a prefix and a range, partly overlapping uncommitted changes
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"abc".to_vec(), b"6".to_vec()).unwrap();
+    dbtx.del_prefix(MAPID.0, b"ac".to_vec()).unwrap();
+    dbtx.del_range(
+        MAPID.0,
+        Bound::Excluded(b"aa".to_vec()),
+        Bound::Included(b"abc".to_vec()),
+    )
+    .unwrap();
+    // Empty and inverted ranges delete nothing
+    let (start, end) = (b"b".to_vec(), b"aa".to_vec());
+    dbtx.del_range(MAPID.0, Bound::Included(start), Bound::Included(end)).unwrap();
+    check_prefix(&dbtx, Data::new(), &[("aa", "0"), ("b", "5")]);
+    assert_eq!(dbtx.get(MAPID.0, b"acb"), Ok(None));
+
+    // Clearing one map leaves the other one intact
+    dbtx.clear(MAPID.1).unwrap();
+    assert_eq!(dbtx.prefix_iter(MAPID.1, Data::new()).unwrap().count(), 0);
+    dbtx.commit().expect("commit to succeed");
+
+    let dbtx = store.transaction_ro().unwrap();
+    check_prefix(&dbtx, Data::new(), &[("aa", "0"), ("b", "5")]);
+    assert_eq!(dbtx.prefix_iter(MAPID.1, Data::new()).unwrap().count(), 0);
+    drop(dbtx);
+
+    // Deleting everything from the start up to a key
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.del_range(MAPID.0, Bound::Unbounded, Bound::Excluded(b"b".to_vec()))
+        .unwrap();
+    dbtx.commit().expect("commit to succeed");
+
+    let dbtx = store.transaction_ro().unwrap();
+    check_prefix(&dbtx, Data::new(), &[("b", "5")]);
+}
+
 tests![
     put_and_abort,
     put_and_commit,
+    put_and_delete_ranges,
     put_and_iterate_delete_some,
+    put_and_iterate_interleaved,
     put_and_iterate_over_prefixes,
+    put_and_iterate_over_ranges,
+    put_and_iterate_rev_over_ff_prefixes,
+    put_and_move_cursor,
+    put_and_roll_back_to_savepoints,
+    put_conditionally,
+    put_into_maps_with_odd_names,
     put_iterator_count_matches,
     put_twice_then_commit_read_last,
     put_two_under_different_keys,
@@ -15,7 +15,7 @@
 
 //! A dead-simple reference implementation of some aspects of a storage backend
 
-use storage_core::{backend, Data, DbMapId};
+use storage_core::{backend, util, Data, DbMapId};
 
 /// A modifying action to apply to a backend or a model
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -36,6 +36,45 @@ impl WriteAction {
     }
 }
 
+/// A cursor movement to apply to a backend cursor or a model cursor
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CursorOp {
+    Seek(Data),
+    First,
+    Last,
+    Next,
+    Prev,
+    Current,
+}
+
+impl CursorOp {
+    /// Apply the operation to a backend cursor
+    pub fn apply(&self, cursor: &mut impl backend::Cursor) -> Option<(Data, Data)> {
+        let result = match self {
+            Self::Seek(key) => cursor.seek(key),
+            Self::First => cursor.first(),
+            Self::Last => cursor.last(),
+            Self::Next => cursor.next(),
+            Self::Prev => cursor.prev(),
+            Self::Current => cursor.current(),
+        };
+        result.expect("cursor operation to succeed")
+    }
+
+    /// Apply the operation to a model cursor
+    pub fn apply_to_model(&self, cursor: &mut util::MapCursor<'_, Data>) -> Option<(Data, Data)> {
+        let result = match self {
+            Self::Seek(key) => cursor.seek(key),
+            Self::First => cursor.first(),
+            Self::Last => cursor.last(),
+            Self::Next => cursor.next(),
+            Self::Prev => cursor.prev(),
+            Self::Current => cursor.current(),
+        };
+        result.map(|(k, v)| (k.to_vec(), v.clone()))
+    }
+}
+
 pub trait ApplyActions {
     /// Apply a sequence of actions to a transaction
     fn apply_actions(&mut self, map_id: DbMapId, iter: impl Iterator<Item = WriteAction>) {
@@ -80,7 +119,8 @@ impl Model {
 
     /// New model obtained by dumping a database in a transaction. May contain uncommitted changes.
     pub fn from_tx<Tx: backend::ReadOps>(tx: &Tx, map_id: DbMapId) -> Self {
-        Model(backend::ReadOps::prefix_iter(tx, map_id, Data::new()).unwrap().collect())
+        let iter = backend::ReadOps::prefix_iter(tx, map_id, Data::new()).unwrap();
+        Model(iter.map(crate::prelude::owned).collect())
     }
 
     /// Get the inner map
@@ -110,11 +150,33 @@ impl Model {
         self.extend(iter)
     }
 
+    /// Delete all entries with keys in given range
+    pub fn del_range(&mut self, start: std::ops::Bound<Data>, end: std::ops::Bound<Data>) {
+        let keys: Vec<Data> = self.range(start, end).map(|(k, _)| k.to_vec()).collect();
+        keys.iter().for_each(|k| {
+            let _ = self.0.remove(k);
+        });
+    }
+
     /// Get value associated with given key
     pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&[u8]> {
         self.0.get(key.as_ref()).map(|v| v.as_ref())
     }
 
+    /// Iterator over key-value pairs with keys in given range
+    pub fn range(
+        &self,
+        start: std::ops::Bound<Data>,
+        end: std::ops::Bound<Data>,
+    ) -> impl '_ + DoubleEndedIterator<Item = (&[u8], &[u8])> {
+        storage_core::util::RangeIter::new(&self.0, start, end).map(|(k, v)| (k, v.as_ref()))
+    }
+
+    /// Cursor over key-value pairs
+    pub fn cursor(&self) -> util::MapCursor<'_, Data> {
+        util::MapCursor::new(&self.0)
+    }
+
     /// Iterator over key-value pairs
     pub fn iter(&self) -> impl '_ + Iterator<Item = (&[u8], &[u8])> {
         self.0.iter().map(|(k, v)| (k.as_ref(), v.as_ref()))
@@ -14,9 +14,9 @@
 // limitations under the License.
 
 // Re-export a bunch of often used items
-pub use crate::model::{ApplyActions, Model, WriteAction};
+pub use crate::model::{ApplyActions, CursorOp, Model, WriteAction};
 pub use storage_core::{
-    backend::{Backend, BackendImpl, Data, ReadOps, TxRo, TxRw, WriteOps},
+    backend::{Backend, BackendImpl, Cursor, Data, Entry, ReadOps, TxRo, TxRw, WriteOps},
     DbDesc, DbMapCount, DbMapDesc, DbMapId, DbMapsData,
 };
 pub use utils::{sync, thread};
@@ -35,6 +35,18 @@ pub fn desc(n: usize) -> DbDesc {
     storage_core::types::construct::db_desc((0..n).map(|i| DbMapDesc::new(format!("map_{i:02}"))))
 }
 
+/// Turn an iterator entry, which may borrow from the database, into owned data. Panics if
+/// reading the entry failed.
+pub fn owned(entry: storage_core::Result<Entry<'_>>) -> (Data, Data) {
+    let (key, val) = entry.expect("iteration to proceed");
+    (key.into_owned(), val.into_owned())
+}
+
+/// Turn an iterator key into owned data, like [owned] does for entries
+pub fn owned_key(key: storage_core::Result<std::borrow::Cow<'_, [u8]>>) -> Data {
+    key.expect("iteration to proceed").into_owned()
+}
+
 /// Run tests with backend using proptest
 pub fn using_proptest<B: Backend, F: BackendFn<B>, S: proptest::prelude::Strategy>(
     source_file: &'static str,
@@ -53,6 +53,32 @@ mod gen {
         ]
     }
 
+    pub fn bound(key_cardinality: u32) -> impl Strategy<Value = std::ops::Bound<Data>> {
+        use std::ops::Bound;
+        prop_oneof![
+            key(key_cardinality).prop_map(Bound::Included),
+            key(key_cardinality).prop_map(Bound::Excluded),
+            Just(Bound::Unbounded),
+        ]
+    }
+
+    pub fn cursor_op(key_cardinality: u32) -> impl Strategy<Value = super::CursorOp> {
+        use super::CursorOp;
+        prop_oneof![
+            key(key_cardinality).prop_map(CursorOp::Seek),
+            Just(CursorOp::First),
+            Just(CursorOp::Last),
+            Just(CursorOp::Next),
+            Just(CursorOp::Prev),
+            Just(CursorOp::Current),
+        ]
+    }
+
+    // Value from a small set, possibly missing, to make conditional writes succeed more often
+    pub fn small_value() -> impl Strategy<Value = Option<Data>> {
+        proptest::option::of((0u8..3).prop_map(|x| vec![x]))
+    }
+
     pub fn actions(
         key_cardinality: u32,
         count: impl Into<proptest::collection::SizeRange>,
@@ -404,14 +430,18 @@ fn prefix_iteration<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
             let model_a = Model::from_actions(actions_a);
             let dbtx = store.transaction_ro().unwrap();
             let iter_a = dbtx.prefix_iter(MAPID.0, vec![b'a']).unwrap();
-            assert!(model_a.into_iter().eq(iter_a));
+            assert!(model_a.clone().into_iter().eq(iter_a.map(owned)));
+            let iter_a_rev = dbtx.prefix_iter_rev(MAPID.0, vec![b'a']).unwrap();
+            assert!(model_a.into_iter().rev().eq(iter_a_rev.map(owned)));
             drop(dbtx);
 
             // Check iteration over keys prefixed "b"
             let model_b = Model::from_actions(actions_b);
             let dbtx = store.transaction_ro().unwrap();
             let iter_b = dbtx.prefix_iter(MAPID.0, vec![b'b']).unwrap();
-            assert!(model_b.into_iter().eq(iter_b));
+            assert!(model_b.clone().into_iter().eq(iter_b.map(owned)));
+            let keys_b = dbtx.prefix_iter_keys(MAPID.0, vec![b'b']).unwrap().map(owned_key);
+            assert!(keys_b.eq(model_b.into_iter().map(|(k, _)| k)));
             drop(dbtx);
 
             // Check there are no entries prefixed "c"
@@ -421,11 +451,14 @@ fn prefix_iteration<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
 
             // Take all entries prefixed "a" and remove them
             let mut dbtx = store.transaction_rw(None).unwrap();
-            let keys_a: Vec<_> =
-                dbtx.prefix_iter(MAPID.0, vec![b'a']).unwrap().map(|(k, _)| k).collect();
+            let keys_a = dbtx.prefix_iter_keys(MAPID.0, vec![b'a']).unwrap();
+            let keys_a: Vec<_> = keys_a.map(owned_key).collect();
             for key in keys_a {
                 dbtx.del(MAPID.0, &key).unwrap();
             }
+            let mut keys_a = dbtx.prefix_iter_keys(MAPID.0, vec![b'a']).unwrap();
+            assert_eq!(keys_a.next(), None);
+            drop(keys_a);
             dbtx.commit().unwrap();
 
             // Check there are no entries prefixed "a"
@@ -436,6 +469,267 @@ fn prefix_iteration<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
     )
 }
 
+fn range_iteration<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        (
+            gen::actions(100, 0..20),
+            gen::actions(100, 0..20),
+            gen::prop::collection::vec((gen::bound(100), gen::bound(100)), 0..10),
+        ),
+        |backend, (committed, uncommitted, ranges)| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            // Populate the database
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, committed.iter().cloned());
+            dbtx.commit().unwrap();
+
+            // Check ranges against the committed state
+            let model = Model::from_actions(committed);
+            let dbtx = store.transaction_ro().unwrap();
+            for (start, end) in &ranges {
+                let iter = dbtx.range_iter(MAPID.0, start.clone(), end.clone()).unwrap();
+                let expected = model.range(start.clone(), end.clone());
+                assert!(iter.map(owned).eq(expected.map(|(k, v)| (k.to_vec(), v.to_vec()))));
+                let iter = dbtx.range_iter_rev(MAPID.0, start.clone(), end.clone()).unwrap();
+                let expected = model.range(start.clone(), end.clone()).rev();
+                assert!(iter.map(owned).eq(expected.map(|(k, v)| (k.to_vec(), v.to_vec()))));
+            }
+            drop(dbtx);
+
+            // Check ranges against uncommitted changes in a transaction
+            let model = {
+                let mut model = model;
+                model.extend(uncommitted.clone());
+                model
+            };
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, uncommitted.into_iter());
+            for (start, end) in &ranges {
+                let iter = dbtx.range_iter(MAPID.0, start.clone(), end.clone()).unwrap();
+                let expected = model.range(start.clone(), end.clone());
+                assert!(iter.map(owned).eq(expected.map(|(k, v)| (k.to_vec(), v.to_vec()))));
+                let iter = dbtx.range_iter_rev(MAPID.0, start.clone(), end.clone()).unwrap();
+                let expected = model.range(start.clone(), end.clone()).rev();
+                assert!(iter.map(owned).eq(expected.map(|(k, v)| (k.to_vec(), v.to_vec()))));
+            }
+            drop(dbtx);
+        },
+    )
+}
+
+fn range_deletion<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        (
+            gen::actions(100, 0..20),
+            gen::actions(100, 0..20),
+            gen::prop::collection::vec(
+                (gen::bound(100), gen::bound(100), gen::actions(100, 0..5)),
+                0..5,
+            ),
+        ),
+        |backend, (committed, uncommitted, ranges)| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            // Populate the database
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, committed.iter().cloned());
+            dbtx.commit().unwrap();
+
+            // Delete ranges on top of uncommitted changes, followed by more writes that may land
+            // in the deleted ranges, checking the state after each step
+            let mut model = Model::from_actions(committed.into_iter().chain(uncommitted.clone()));
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, uncommitted.into_iter());
+            for (start, end, actions) in ranges {
+                dbtx.del_range(MAPID.0, start.clone(), end.clone()).unwrap();
+                model.del_range(start, end);
+                dbtx.apply_actions(MAPID.0, actions.iter().cloned());
+                model.apply_actions(actions.into_iter());
+                assert_eq!(Model::from_tx(&dbtx, MAPID.0), model);
+                for (key, val) in model.iter() {
+                    assert_eq!(dbtx.get(MAPID.0, key).unwrap().as_deref(), Some(val));
+                }
+
+                // The cursor sees the same entries in both directions
+                let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+                let mut model_cursor = model.cursor();
+                use CursorOp::{First, Last, Next, Prev};
+                for op in [First, Next, Next, Last, Prev, Prev, Prev] {
+                    assert_eq!(op.apply(&mut cursor), op.apply_to_model(&mut model_cursor));
+                }
+            }
+            dbtx.commit().unwrap();
+
+            assert_eq!(Model::from_db(&store, MAPID.0), model);
+        },
+    )
+}
+
+fn range_deletion_rollback<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        (
+            gen::actions(100, 0..20),
+            gen::prop::collection::vec((gen::bound(100), gen::bound(100)), 0..5),
+            gen::actions(100, 0..10),
+            gen::prop::collection::vec((gen::bound(100), gen::bound(100)), 0..5),
+        ),
+        |backend, (initial, discarded, writes, kept)| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, initial.iter().cloned());
+            dbtx.commit().unwrap();
+
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, writes.iter().cloned());
+            let mut model = Model::from_tx(&dbtx, MAPID.0);
+
+            // Deleted ranges and clearing the map are undone by rolling back
+            dbtx.savepoint().unwrap();
+            for (start, end) in discarded {
+                dbtx.del_range(MAPID.0, start, end).unwrap();
+            }
+            dbtx.savepoint().unwrap();
+            dbtx.clear(MAPID.0).unwrap();
+            assert_eq!(Model::from_tx(&dbtx, MAPID.0), Model::new());
+            dbtx.release().unwrap();
+            dbtx.rollback_to().unwrap();
+            assert_eq!(Model::from_tx(&dbtx, MAPID.0), model);
+
+            // Deleted ranges survive releasing the savepoint
+            dbtx.savepoint().unwrap();
+            for (start, end) in kept {
+                dbtx.del_range(MAPID.0, start.clone(), end.clone()).unwrap();
+                model.del_range(start, end);
+            }
+            dbtx.release().unwrap();
+            assert_eq!(Model::from_tx(&dbtx, MAPID.0), model);
+            dbtx.commit().unwrap();
+
+            assert_eq!(Model::from_db(&store, MAPID.0), model);
+        },
+    )
+}
+
+fn cursor_movement<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        (
+            gen::actions(100, 0..20),
+            gen::actions(100, 0..20),
+            gen::prop::collection::vec(gen::cursor_op(100), 0..30),
+        ),
+        |backend, (committed, uncommitted, ops)| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            // Populate the database
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, committed.iter().cloned());
+            dbtx.commit().unwrap();
+
+            // Move the cursor over the committed state and check each step against the model
+            let model = Model::from_actions(committed);
+            let dbtx = store.transaction_ro().unwrap();
+            let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+            let mut model_cursor = model.cursor();
+            for op in &ops {
+                assert_eq!(op.apply(&mut cursor), op.apply_to_model(&mut model_cursor));
+            }
+            drop(cursor);
+            drop(dbtx);
+
+            // Do the same with uncommitted changes in a transaction
+            let model = {
+                let mut model = model;
+                model.extend(uncommitted.clone());
+                model
+            };
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, uncommitted.into_iter());
+            let mut cursor = dbtx.cursor(MAPID.0).unwrap();
+            let mut model_cursor = model.cursor();
+            for op in &ops {
+                assert_eq!(op.apply(&mut cursor), op.apply_to_model(&mut model_cursor));
+            }
+        },
+    )
+}
+
+fn savepoint_rollback_and_release<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        (
+            gen::actions(100, 0..20),
+            gen::actions(100, 0..20),
+            gen::actions(100, 0..20),
+        ),
+        |backend, (initial, discarded, kept)| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            dbtx.apply_actions(MAPID.0, initial.iter().cloned());
+            let initial_model = Model::from_tx(&dbtx, MAPID.0);
+
+            // Changes rolled back to a savepoint leave no trace
+            dbtx.savepoint().unwrap();
+            dbtx.apply_actions(MAPID.0, discarded.into_iter());
+            dbtx.rollback_to().unwrap();
+            assert_eq!(Model::from_tx(&dbtx, MAPID.0), initial_model);
+
+            // Changes made since a released savepoint are kept
+            dbtx.savepoint().unwrap();
+            dbtx.apply_actions(MAPID.0, kept.iter().cloned());
+            dbtx.release().unwrap();
+            dbtx.commit().unwrap();
+
+            let model = Model::from_actions(initial.into_iter().chain(kept));
+            assert_eq!(Model::from_db(&store, MAPID.0), model);
+        },
+    )
+}
+
+fn conditional_writes<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
+    using_proptest(
+        file!(),
+        backend_fn,
+        gen::prop::collection::vec(
+            (gen::key(10), gen::small_value(), gen::small_value()),
+            0..50,
+        ),
+        |backend, swaps| {
+            let store = backend.open(desc(1)).expect("db open to succeed");
+
+            let mut model = Model::new();
+            let mut dbtx = store.transaction_rw(None).unwrap();
+            for (key, expected, new) in swaps {
+                let matches = model.get(&key) == expected.as_deref();
+                let swapped = dbtx
+                    .compare_and_swap(MAPID.0, key.clone(), expected.as_deref(), new.clone())
+                    .unwrap();
+                assert_eq!(swapped, matches);
+                if matches {
+                    model.apply_action(match new {
+                        Some(val) => WriteAction::Put(key, val),
+                        None => WriteAction::Del(key),
+                    });
+                }
+            }
+            dbtx.commit().unwrap();
+
+            assert_eq!(Model::from_db(&store, MAPID.0), model);
+        },
+    )
+}
+
 fn post_commit_consistency<B: Backend, F: BackendFn<B>>(backend_fn: Arc<F>) {
     using_proptest(
         file!(),
@@ -462,10 +756,16 @@ tests![
     add_and_delete_some,
     add_modify_abort_modify_commit,
     add_modify_abort_replay_commit,
+    conditional_writes,
+    cursor_movement,
     db_writes_do_not_interfere,
     empty_after_abort,
     last_write_wins,
     overwrite_and_abort,
     post_commit_consistency,
     prefix_iteration,
+    range_deletion,
+    range_deletion_rollback,
+    range_iteration,
+    savepoint_rollback_and_release,
 ];
@@ -0,0 +1,193 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cursor for RW transactions, merging the underlying database with the uncommitted changes
+
+use std::cmp::Ordering;
+
+use crate::{
+    backend::{self, ReadOps},
+    util::MapCursor,
+};
+
+use super::{Data, DbMapId, Deleted, DeltaMap, TxRw};
+
+type DataPair = (Data, Data);
+
+/// Cursor over the underlying database with the transaction deltas applied on top.
+///
+/// The position is tracked by the key of the current entry. Each move re-positions both the
+/// database cursor and the delta cursor relative to that key and then merges the two.
+pub struct Cursor<'i, T: ReadOps + 'i> {
+    db: T::Cursor<'i>,
+    deltas: MapCursor<'i, Option<Data>>,
+    deleted: &'i Deleted,
+    current: Option<DataPair>,
+}
+
+impl<'i, T: ReadOps + 'i> Cursor<'i, T> {
+    fn set(&mut self, current: Option<DataPair>) -> crate::Result<Option<DataPair>> {
+        self.current = current;
+        Ok(self.current.clone())
+    }
+
+    /// Merge the two cursors, starting from given positions, until a live entry is found
+    fn settle(
+        &mut self,
+        mut db_item: Option<DataPair>,
+        mut delta_item: Option<(&'i [u8], &'i Option<Data>)>,
+        forward: bool,
+    ) -> crate::Result<Option<DataPair>> {
+        loop {
+            db_item = self.skip_deleted(db_item, forward)?;
+            let (delta_key, delta_val) = match delta_item {
+                Some(item) => item,
+                None => return self.set(db_item),
+            };
+
+            // Determine whether the database entry comes first in the direction of movement
+            let order = match &db_item {
+                Some((db_key, _)) if forward => db_key.as_slice().cmp(delta_key),
+                Some((db_key, _)) => delta_key.cmp(db_key.as_slice()),
+                None => Ordering::Greater,
+            };
+            if order == Ordering::Less {
+                return self.set(db_item);
+            }
+
+            // The delta entry takes precedence. If it is a deletion, skip it, together with the
+            // database entry it deletes, if any.
+            if let Some(val) = delta_val {
+                return self.set(Some((delta_key.to_vec(), val.clone())));
+            }
+            if order == Ordering::Equal {
+                db_item = self.step_db(forward)?;
+            }
+            delta_item = self.step_deltas(forward);
+        }
+    }
+
+    /// Move the database cursor past the entries deleted by the transaction
+    fn skip_deleted(
+        &mut self,
+        mut db_item: Option<DataPair>,
+        forward: bool,
+    ) -> crate::Result<Option<DataPair>> {
+        if self.deleted.cleared {
+            return Ok(None);
+        }
+        while db_item.as_ref().is_some_and(|(key, _)| self.deleted.contains(key)) {
+            db_item = self.step_db(forward)?;
+        }
+        Ok(db_item)
+    }
+
+    fn step_db(&mut self, forward: bool) -> crate::Result<Option<DataPair>> {
+        use backend::Cursor;
+        if forward {
+            self.db.next()
+        } else {
+            self.db.prev()
+        }
+    }
+
+    fn step_deltas(&mut self, forward: bool) -> Option<(&'i [u8], &'i Option<Data>)> {
+        if forward {
+            self.deltas.next()
+        } else {
+            self.deltas.prev()
+        }
+    }
+}
+
+impl<'i, T: ReadOps + 'i> backend::Cursor for Cursor<'i, T> {
+    fn seek(&mut self, key: &[u8]) -> crate::Result<Option<DataPair>> {
+        let db_item = self.db.seek(key)?;
+        let delta_item = self.deltas.seek(key);
+        self.settle(db_item, delta_item, true)
+    }
+
+    fn first(&mut self) -> crate::Result<Option<DataPair>> {
+        let db_item = self.db.first()?;
+        let delta_item = self.deltas.first();
+        self.settle(db_item, delta_item, true)
+    }
+
+    fn last(&mut self) -> crate::Result<Option<DataPair>> {
+        let db_item = self.db.last()?;
+        let delta_item = self.deltas.last();
+        self.settle(db_item, delta_item, false)
+    }
+
+    fn next(&mut self) -> crate::Result<Option<DataPair>> {
+        let key = match &self.current {
+            Some((key, _)) => key.clone(),
+            None => return self.first(),
+        };
+
+        // Position both cursors at the first entry past the current key
+        let mut db_item = self.db.seek(&key)?;
+        if db_item.as_ref().is_some_and(|(k, _)| *k == key) {
+            db_item = self.db.next()?;
+        }
+        let mut delta_item = self.deltas.seek(&key);
+        if delta_item.is_some_and(|(k, _)| k == key.as_slice()) {
+            delta_item = self.deltas.next();
+        }
+
+        self.settle(db_item, delta_item, true)
+    }
+
+    fn prev(&mut self) -> crate::Result<Option<DataPair>> {
+        let key = match &self.current {
+            Some((key, _)) => key.clone(),
+            None => return self.last(),
+        };
+
+        // Position both cursors at the last entry before the current key
+        let db_item = match self.db.seek(&key)? {
+            Some(_) => self.db.prev()?,
+            None => self.db.last()?,
+        };
+        let delta_item = match self.deltas.seek(&key) {
+            Some(_) => self.deltas.prev(),
+            None => self.deltas.last(),
+        };
+
+        self.settle(db_item, delta_item, false)
+    }
+
+    fn current(&self) -> crate::Result<Option<DataPair>> {
+        Ok(self.current.clone())
+    }
+}
+
+/// Create the cursor
+pub fn cursor<'tx, 'i, 'm: 'i, T: ReadOps>(
+    tx: &'m TxRw<'tx, T>,
+    map_id: DbMapId,
+) -> crate::Result<Cursor<'i, T>> {
+    let db = tx.db.cursor(map_id)?;
+    let deltas: &DeltaMap = &tx.deltas[map_id].entries;
+    let deltas = MapCursor::new(deltas);
+    let deleted = &tx.deltas[map_id].deleted;
+    let current = None;
+    Ok(Cursor {
+        db,
+        deltas,
+        deleted,
+        current,
+    })
+}
@@ -16,6 +16,7 @@
 //! A simple adaptor to add transaction capability to a type that only implements the basic
 //! read/write operations, giving a full-featured (albeit not necessarily efficient) backend.
 
+mod cursor_rw;
 mod prefix_iter_rw;
 
 use crate::{
@@ -24,7 +25,7 @@ use crate::{
     Data, DbDesc, DbMapCount, DbMapId, DbMapsData,
 };
 
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, ops::Bound};
 use utils::{const_value::ConstValue, sync};
 
 // Read-only transaction just holds a read lock to the database
@@ -32,6 +33,10 @@ pub struct TxRo<'tx, T>(sync::RwLockReadGuard<'tx, T>);
 
 impl<'tx, T: ReadOps> ReadOps for TxRo<'tx, T> {
     type PrefixIter<'i> = T::PrefixIter<'i> where Self: 'i;
+    type PrefixKeyIter<'i> = T::PrefixKeyIter<'i> where Self: 'i;
+    type RangeIter<'i> = T::RangeIter<'i> where Self: 'i;
+    type RangeIterRev<'i> = T::RangeIterRev<'i> where Self: 'i;
+    type Cursor<'i> = T::Cursor<'i> where Self: 'i;
 
     fn get(&self, map_id: DbMapId, key: &[u8]) -> crate::Result<Option<Cow<[u8]>>> {
         self.0.get(map_id, key)
@@ -40,6 +45,36 @@ impl<'tx, T: ReadOps> ReadOps for TxRo<'tx, T> {
     fn prefix_iter(&self, map_id: DbMapId, prefix: Data) -> crate::Result<Self::PrefixIter<'_>> {
         self.0.prefix_iter(map_id, prefix)
     }
+
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> crate::Result<Self::PrefixKeyIter<'_>> {
+        self.0.prefix_iter_keys(map_id, prefix)
+    }
+
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIter<'_>> {
+        self.0.range_iter(map_id, start, end)
+    }
+
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIterRev<'_>> {
+        self.0.range_iter_rev(map_id, start, end)
+    }
+
+    fn cursor(&self, map_id: DbMapId) -> crate::Result<Self::Cursor<'_>> {
+        self.0.cursor(map_id)
+    }
 }
 
 impl<'tx, T: ReadOps> backend::TxRo for TxRo<'tx, T> {}
@@ -47,35 +82,145 @@ impl<'tx, T: ReadOps> backend::TxRo for TxRo<'tx, T> {}
 // Tracker for database changes
 type DeltaMap = BTreeMap<Data, Option<Data>>;
 
+// Delta map entries overwritten since a savepoint has been created, used to roll back to it.
+// The outer option records whether the key was present in the delta map at that time.
+type UndoMap = BTreeMap<Data, Option<Option<Data>>>;
+
+/// Keys deleted from the underlying database wholesale, by deleting ranges or clearing the map
+#[derive(Default)]
+struct Deleted {
+    cleared: bool,
+    ranges: Vec<(Bound<Data>, Bound<Data>)>,
+}
+
+impl Deleted {
+    /// Check whether the key in the underlying database has been deleted
+    fn contains(&self, key: &[u8]) -> bool {
+        self.cleared
+            || self.ranges.iter().any(|(start, end)| {
+                !crate::util::is_before_start(start, key) && !crate::util::is_past_end(end, key)
+            })
+    }
+}
+
+/// Changes to a single map. Entries in the delta map take precedence over the deleted ranges.
+#[derive(Default)]
+struct Delta {
+    entries: DeltaMap,
+    deleted: Deleted,
+}
+
+/// Information needed to roll a map back to a savepoint. Deleted ranges are only ever appended,
+/// so it is sufficient to remember how many there were.
+struct Undo {
+    entries: UndoMap,
+    num_ranges: usize,
+    cleared: bool,
+}
+
+impl Undo {
+    fn new(delta: &Delta) -> Self {
+        Self {
+            entries: UndoMap::new(),
+            num_ranges: delta.deleted.ranges.len(),
+            cleared: delta.deleted.cleared,
+        }
+    }
+}
+
 // RW transaction holds a write lock to the database and a list of changes performed
 pub struct TxRw<'tx, T> {
     db: sync::RwLockWriteGuard<'tx, T>,
-    deltas: DbMapsData<DeltaMap>,
+    deltas: DbMapsData<Delta>,
+    savepoints: Vec<DbMapsData<Undo>>,
 }
 
 impl<'tx, T> TxRw<'tx, T> {
     fn update(&mut self, map_id: DbMapId, key: Data, val: Option<Data>) -> crate::Result<()> {
-        self.deltas[map_id].insert(key, val);
+        match self.savepoints.last_mut() {
+            Some(undo) => {
+                let prev = self.deltas[map_id].entries.insert(key.clone(), val);
+                undo[map_id].entries.entry(key).or_insert(prev);
+            }
+            None => {
+                self.deltas[map_id].entries.insert(key, val);
+            }
+        }
         Ok(())
     }
+
+    /// Record entries removed from the delta map, e.g. because they are covered by a deleted
+    /// range, so they can be restored by rolling back to the last savepoint
+    fn forget(&mut self, map_id: DbMapId, entries: impl IntoIterator<Item = (Data, Option<Data>)>) {
+        if let Some(undo) = self.savepoints.last_mut() {
+            for (key, val) in entries {
+                undo[map_id].entries.entry(key).or_insert(Some(val));
+            }
+        }
+    }
+
+    /// Number of changes tracked by the transaction across all maps, counting both individual
+    /// keys and deleted ranges
+    pub fn num_tracked_changes(&self) -> usize {
+        let deltas = self.deltas.transform(|delta| {
+            delta.entries.len() + delta.deleted.ranges.len() + usize::from(delta.deleted.cleared)
+        });
+        deltas.into_iter_with_id().map(|(_, num)| num).sum()
+    }
 }
 
 impl<'tx, T: ReadOps> ReadOps for TxRw<'tx, T> {
-    type PrefixIter<'i> = prefix_iter_rw::Iter<'i, T> where Self: 'i;
+    type PrefixIter<'i> = prefix_iter_rw::PrefixIter<'i, T> where Self: 'i;
+    type PrefixKeyIter<'i> = prefix_iter_rw::PrefixKeyIter<'i, T> where Self: 'i;
+    type RangeIter<'i> = prefix_iter_rw::RangeIter<'i, T> where Self: 'i;
+    type RangeIterRev<'i> = prefix_iter_rw::RangeIterRev<'i, T> where Self: 'i;
+    type Cursor<'i> = cursor_rw::Cursor<'i, T> where Self: 'i;
 
     fn get(&self, map_id: DbMapId, key: &[u8]) -> crate::Result<Option<Cow<[u8]>>> {
-        self.deltas[map_id].get(key).map_or_else(
-            || self.db.get(map_id, key),
-            |x| Ok(x.as_deref().map(|p| p.into())),
-        )
+        let delta = &self.deltas[map_id];
+        match delta.entries.get(key) {
+            Some(val) => Ok(val.as_deref().map(|p| p.into())),
+            None if delta.deleted.contains(key) => Ok(None),
+            None => self.db.get(map_id, key),
+        }
     }
 
     fn prefix_iter(&self, map_id: DbMapId, prefix: Data) -> crate::Result<Self::PrefixIter<'_>> {
-        prefix_iter_rw::iter(self, map_id, prefix)
+        prefix_iter_rw::prefix_iter(self, map_id, prefix)
+    }
+
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> crate::Result<Self::PrefixKeyIter<'_>> {
+        prefix_iter_rw::prefix_iter_keys(self, map_id, prefix)
+    }
+
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIter<'_>> {
+        prefix_iter_rw::range_iter(self, map_id, start, end)
+    }
+
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIterRev<'_>> {
+        prefix_iter_rw::range_iter_rev(self, map_id, start, end)
+    }
+
+    fn cursor(&self, map_id: DbMapId) -> crate::Result<Self::Cursor<'_>> {
+        cursor_rw::cursor(self, map_id)
     }
 }
 
-impl<'tx, T> WriteOps for TxRw<'tx, T> {
+impl<'tx, T: ReadOps> WriteOps for TxRw<'tx, T> {
     fn put(&mut self, map_id: DbMapId, key: Data, val: Data) -> crate::Result<()> {
         self.update(map_id, key, Some(val))
     }
@@ -83,12 +228,64 @@ impl<'tx, T> WriteOps for TxRw<'tx, T> {
     fn del(&mut self, map_id: DbMapId, key: &[u8]) -> crate::Result<()> {
         self.update(map_id, key.to_vec(), None)
     }
+
+    fn del_range(
+        &mut self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<()> {
+        // Record the range rather than each key it covers. The changes made so far in the range
+        // are superseded by the deletion.
+        let delta = &mut self.deltas[map_id];
+        let keys: Vec<Data> =
+            crate::util::RangeIter::new(&delta.entries, start.clone(), end.clone())
+                .map(|(key, _)| key.to_vec())
+                .collect();
+        let removed: Vec<_> =
+            keys.into_iter().filter_map(|key| delta.entries.remove_entry(&key)).collect();
+        if !delta.deleted.cleared {
+            delta.deleted.ranges.push((start, end));
+        }
+        self.forget(map_id, removed);
+        Ok(())
+    }
+
+    fn clear(&mut self, map_id: DbMapId) -> crate::Result<()> {
+        let delta = &mut self.deltas[map_id];
+        let removed = std::mem::take(&mut delta.entries);
+        delta.deleted.cleared = true;
+        self.forget(map_id, removed);
+        Ok(())
+    }
+
+    fn compare_and_swap(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> crate::Result<bool> {
+        // The transaction holds the write lock, so nobody else can interfere in between
+        let matches = self.get(map_id, &key)?.as_deref() == expected;
+        if matches {
+            self.update(map_id, key, new)?;
+        }
+        Ok(matches)
+    }
 }
 
 impl<'tx, T: ReadOps + WriteOps> backend::TxRw for TxRw<'tx, T> {
     fn commit(mut self) -> crate::Result<()> {
-        for (idx, kvmap) in self.deltas.into_iter_with_id() {
-            for (key, val) in kvmap {
+        for (idx, delta) in self.deltas.into_iter_with_id() {
+            if delta.deleted.cleared {
+                self.db.clear(idx)?;
+            } else {
+                for (start, end) in delta.deleted.ranges {
+                    self.db.del_range(idx, start, end)?;
+                }
+            }
+            for (key, val) in delta.entries {
                 match val {
                     None => self.db.del(idx, &key)?,
                     Some(val) => self.db.put(idx, key, val)?,
@@ -97,6 +294,41 @@ impl<'tx, T: ReadOps + WriteOps> backend::TxRw for TxRw<'tx, T> {
         }
         Ok(())
     }
+
+    fn savepoint(&mut self) -> crate::Result<()> {
+        self.savepoints.push(self.deltas.transform(Undo::new));
+        Ok(())
+    }
+
+    fn rollback_to(&mut self) -> crate::Result<()> {
+        let undo = self.savepoints.pop().ok_or(crate::error::Fatal::NoSavepoint)?;
+        for (map_id, undo) in undo.into_iter_with_id() {
+            let delta = &mut self.deltas[map_id];
+            for (key, prev) in undo.entries {
+                match prev {
+                    Some(prev) => delta.entries.insert(key, prev),
+                    None => delta.entries.remove(&key),
+                };
+            }
+            delta.deleted.ranges.truncate(undo.num_ranges);
+            delta.deleted.cleared = undo.cleared;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> crate::Result<()> {
+        let undo = self.savepoints.pop().ok_or(crate::error::Fatal::NoSavepoint)?;
+        // The entries recorded by an enclosing savepoint predate this one and take precedence.
+        // So does its record of the deleted ranges.
+        if let Some(outer) = self.savepoints.last_mut() {
+            for (map_id, undo) in undo.into_iter_with_id() {
+                for (key, prev) in undo.entries {
+                    outer[map_id].entries.entry(key).or_insert(prev);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct TransactionLockImpl<T> {
@@ -134,7 +366,8 @@ impl<T: CoreOps + Sync + Send + 'static> backend::BackendImpl for TransactionLoc
     fn transaction_rw(&self, _size: Option<usize>) -> crate::Result<Self::TxRw<'_>> {
         Ok(TxRw {
             db: self.db.write().expect("lock to be alive"),
-            deltas: DbMapsData::new(*self.num_maps, |_| BTreeMap::new()),
+            deltas: DbMapsData::new(*self.num_maps, |_| Delta::default()),
+            savepoints: Vec::new(),
         })
     }
 }
@@ -13,54 +13,214 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Internal functions and types used in the implementation of prefix iterator for RW transactions
+//! Internal functions and types used in the implementation of prefix and range iterators for RW
+//! transactions
 
-use crate::backend::ReadOps;
+use std::{borrow::Cow, iter::Rev, ops::Bound};
 
-use super::{Data, DbMapId, TxRw};
+use crate::{
+    backend::{Entry, ReadOps},
+    util::EndOnError,
+};
+
+use super::{Data, DbMapId, Deleted, TxRw};
 use itertools::EitherOrBoth;
 
 // The prefix iterator type for mutable transaction is a fairly complicated type. Here, we
 // introduce a bunch of type aliases to simplify its definition a bit.
-type DataPair = (Data, Data);
+type DbItem<'i> = crate::Result<Entry<'i>>;
+type DbKeyItem<'i> = crate::Result<Cow<'i, [u8]>>;
 type DataPairRef<'a> = (&'a [u8], &'a Option<Data>);
-type KeyCompareFn = fn(&DataPair, &DataPairRef<'_>) -> std::cmp::Ordering;
-type ItemMergeFn = fn(EitherOrBoth<DataPair, DataPairRef<'_>>) -> Option<DataPair>;
-type DbIter<'i, T> = <T as ReadOps>::PrefixIter<'i>;
-type DeltaIter<'i> = crate::util::PrefixIter<'i, Option<Data>>;
-type JoinIter<'i, T> = itertools::MergeJoinBy<DbIter<'i, T>, DeltaIter<'i>, KeyCompareFn>;
-pub type Iter<'i, T> = std::iter::FilterMap<JoinIter<'i, T>, ItemMergeFn>;
+type KeyCompareFn = fn(&DbItem<'_>, &DataPairRef<'_>) -> std::cmp::Ordering;
+type ItemMergeFn<'i> = fn(EitherOrBoth<DbItem<'i>, DataPairRef<'i>>) -> Option<DbItem<'i>>;
+type KeyOnlyCompareFn = fn(&DbKeyItem<'_>, &DataPairRef<'_>) -> std::cmp::Ordering;
+type KeyOnlyMergeFn<'i> = fn(EitherOrBoth<DbKeyItem<'i>, DataPairRef<'i>>) -> Option<DbKeyItem<'i>>;
+type JoinIter<DbIter, DeltaIter> = itertools::MergeJoinBy<DbIter, DeltaIter, KeyCompareFn>;
+type Iter<'i, DbIter, DeltaIter> =
+    EndOnError<std::iter::FilterMap<JoinIter<Visible<'i, DbIter>, DeltaIter>, ItemMergeFn<'i>>>;
+pub type PrefixIter<'i, T> =
+    Iter<'i, <T as ReadOps>::PrefixIter<'i>, crate::util::PrefixIter<'i, Option<Data>>>;
+pub type PrefixKeyIter<'i, T> = EndOnError<
+    std::iter::FilterMap<
+        itertools::MergeJoinBy<
+            Visible<'i, <T as ReadOps>::PrefixKeyIter<'i>>,
+            crate::util::PrefixIter<'i, Option<Data>>,
+            KeyOnlyCompareFn,
+        >,
+        KeyOnlyMergeFn<'i>,
+    >,
+>;
+pub type RangeIter<'i, T> =
+    Iter<'i, <T as ReadOps>::RangeIter<'i>, crate::util::RangeIter<'i, Option<Data>>>;
+pub type RangeIterRev<'i, T> =
+    Iter<'i, <T as ReadOps>::RangeIterRev<'i>, Rev<crate::util::RangeIter<'i, Option<Data>>>>;
+
+/// Access to the key of an item produced by the underlying database iterator
+pub trait ItemKey {
+    /// The key, or `None` if the item is an error
+    fn key(&self) -> Option<&[u8]>;
+}
+
+impl ItemKey for DbItem<'_> {
+    fn key(&self) -> Option<&[u8]> {
+        self.as_ref().ok().map(|(k, _)| k.as_ref())
+    }
+}
+
+impl ItemKey for DbKeyItem<'_> {
+    fn key(&self) -> Option<&[u8]> {
+        self.as_ref().ok().map(|k| k.as_ref())
+    }
+}
+
+/// Iterator over the underlying database, skipping the entries deleted by the transaction.
+/// If the map has been cleared, the underlying database is not consulted at all.
+pub struct Visible<'i, I> {
+    iter: Option<I>,
+    deleted: &'i Deleted,
+}
 
-/// Function to compare key-value entries by the key
-fn comparator((a, _): &DataPair, (b, _): &DataPairRef<'_>) -> std::cmp::Ordering {
-    a.as_slice().cmp(b)
+impl<'i, I> Visible<'i, I> {
+    fn new(
+        deleted: &'i Deleted,
+        make_iter: impl FnOnce() -> crate::Result<I>,
+    ) -> crate::Result<Self> {
+        let iter = if deleted.cleared {
+            None
+        } else {
+            Some(make_iter()?)
+        };
+        Ok(Self { iter, deleted })
+    }
+}
+
+impl<'i, I: Iterator> Iterator for Visible<'i, I>
+where
+    I::Item: ItemKey,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let deleted = self.deleted;
+        self.iter
+            .as_mut()?
+            .find(|item| item.key().map_or(true, |key| !deleted.contains(key)))
+    }
+}
+
+/// Function to compare key-value entries by the key. Errors from the database are ordered first
+/// so they are passed on right away.
+fn comparator(a: &DbItem<'_>, (b, _): &DataPairRef<'_>) -> std::cmp::Ordering {
+    a.as_ref().map_or(std::cmp::Ordering::Less, |(a, _)| a.as_ref().cmp(b))
+}
+
+/// Function to compare key-value entries by the key, for iteration in descending order
+fn comparator_rev(a: &DbItem<'_>, (b, _): &DataPairRef<'_>) -> std::cmp::Ordering {
+    a.as_ref().map_or(std::cmp::Ordering::Less, |(a, _)| {
+        a.as_ref().cmp(b).reverse()
+    })
 }
 
 /// How to merge the items if the keys collide
-fn merger(item: EitherOrBoth<DataPair, DataPairRef<'_>>) -> Option<(Data, Data)> {
+fn merger<'i>(item: EitherOrBoth<DbItem<'i>, DataPairRef<'i>>) -> Option<DbItem<'i>> {
     match item {
         // Item only in original db, just present it
         EitherOrBoth::Left(l) => Some(l),
         // If the entry is present in both database and the delta map, the delta map takes
         // precedence. If it only is in the delta map, just take that.
         EitherOrBoth::Right((k, v)) | EitherOrBoth::Both(_, (k, v)) => {
-            v.as_ref().map(|v| (k.to_vec(), v.clone()))
+            v.as_deref().map(|v| Ok((k.into(), v.into())))
+        }
+    }
+}
+
+/// Function to compare a key with a delta map entry by the key
+fn key_comparator(a: &DbKeyItem<'_>, (b, _): &DataPairRef<'_>) -> std::cmp::Ordering {
+    a.as_ref().map_or(std::cmp::Ordering::Less, |a| a.as_ref().cmp(b))
+}
+
+/// How to merge the keys if they collide, like [merger] but without the values
+fn key_merger<'i>(item: EitherOrBoth<DbKeyItem<'i>, DataPairRef<'i>>) -> Option<DbKeyItem<'i>> {
+    match item {
+        EitherOrBoth::Left(k) => Some(k),
+        EitherOrBoth::Right((k, v)) | EitherOrBoth::Both(_, (k, v)) => {
+            v.as_ref().map(|_| Ok(k.into()))
         }
     }
 }
 
-/// Create the iterator
-pub fn iter<'tx, 'i, 'm: 'i, T: ReadOps>(
+/// Merge the entries from the underlying database and the delta map, ordered by given comparator
+fn merge<'i, DbIter, DeltaIter>(
+    db_iter: Visible<'i, DbIter>,
+    delta_iter: DeltaIter,
+    comparator: KeyCompareFn,
+) -> Iter<'i, DbIter, DeltaIter>
+where
+    Visible<'i, DbIter>: Iterator<Item = DbItem<'i>>,
+    DeltaIter: Iterator<Item = DataPairRef<'i>>,
+{
+    let iter = itertools::merge_join_by(db_iter, delta_iter, comparator);
+    EndOnError::new(iter.filter_map(merger as ItemMergeFn<'i>))
+}
+
+/// Create the prefix iterator
+pub fn prefix_iter<'tx, 'i, 'm: 'i, T: ReadOps>(
     tx: &'m TxRw<'tx, T>,
     map_id: DbMapId,
     prefix: Data,
-) -> crate::Result<Iter<'i, T>> {
+) -> crate::Result<PrefixIter<'i, T>> {
+    // Initialize the iterator over the underlying db and the deltas
+    let delta = &tx.deltas[map_id];
+    let db_iter = Visible::new(&delta.deleted, || tx.db.prefix_iter(map_id, prefix.clone()))?;
+    let delta_iter = crate::util::PrefixIter::new(&delta.entries, prefix);
+    Ok(merge(db_iter, delta_iter, comparator))
+}
+
+/// Create the range iterator
+pub fn range_iter<'tx, 'i, 'm: 'i, T: ReadOps>(
+    tx: &'m TxRw<'tx, T>,
+    map_id: DbMapId,
+    start: Bound<Data>,
+    end: Bound<Data>,
+) -> crate::Result<RangeIter<'i, T>> {
     // Initialize the iterator over the underlying db and the deltas
-    let db_iter = tx.db.prefix_iter(map_id, prefix.clone())?;
-    let delta_iter = crate::util::PrefixIter::new(&tx.deltas[map_id], prefix);
+    let delta = &tx.deltas[map_id];
+    let db_iter = Visible::new(&delta.deleted, || {
+        tx.db.range_iter(map_id, start.clone(), end.clone())
+    })?;
+    let delta_iter = crate::util::RangeIter::new(&delta.entries, start, end);
+    Ok(merge(db_iter, delta_iter, comparator))
+}
 
-    // Merge the entries from the two iterators
-    let iter = itertools::merge_join_by(db_iter, delta_iter, comparator as KeyCompareFn)
-        .filter_map(merger as ItemMergeFn);
-    Ok(iter)
+/// Create the reverse range iterator
+pub fn range_iter_rev<'tx, 'i, 'm: 'i, T: ReadOps>(
+    tx: &'m TxRw<'tx, T>,
+    map_id: DbMapId,
+    start: Bound<Data>,
+    end: Bound<Data>,
+) -> crate::Result<RangeIterRev<'i, T>> {
+    // Initialize the iterator over the underlying db and the deltas
+    let delta = &tx.deltas[map_id];
+    let db_iter = Visible::new(&delta.deleted, || {
+        tx.db.range_iter_rev(map_id, start.clone(), end.clone())
+    })?;
+    let delta_iter = crate::util::RangeIter::new(&delta.entries, start, end).rev();
+    Ok(merge(db_iter, delta_iter, comparator_rev))
+}
+
+/// Create the prefix iterator over keys only
+pub fn prefix_iter_keys<'tx, 'i, 'm: 'i, T: ReadOps>(
+    tx: &'m TxRw<'tx, T>,
+    map_id: DbMapId,
+    prefix: Data,
+) -> crate::Result<PrefixKeyIter<'i, T>> {
+    let delta = &tx.deltas[map_id];
+    let db_iter = Visible::new(&delta.deleted, || {
+        tx.db.prefix_iter_keys(map_id, prefix.clone())
+    })?;
+    let delta_iter = crate::util::PrefixIter::new(&delta.entries, prefix);
+    let iter = itertools::merge_join_by(db_iter, delta_iter, key_comparator as KeyOnlyCompareFn);
+    Ok(EndOnError::new(
+        iter.filter_map(key_merger as KeyOnlyMergeFn<'i>),
+    ))
 }
@@ -15,16 +15,42 @@
 
 //! Low-level interface implemented by storage backends.
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap, ops::Bound};
 
 use utils::shallow_clone::ShallowClone;
 
 pub use crate::{Data, DbDesc, DbMapId};
 
-/// Read-only database operations
+/// Key-value entry yielded by iterators. Backends borrow the data from the database where possible
+/// and fall back to owned data otherwise.
+pub type Entry<'i> = (Cow<'i, [u8]>, Cow<'i, [u8]>);
+
+/// Read-only database operations.
+///
+/// Iterators yield an error if an entry cannot be read, after which the iteration ends.
 pub trait ReadOps {
     /// The prefix iterator type
-    type PrefixIter<'i>: Iterator<Item = (Data, Data)> + 'i
+    type PrefixIter<'i>: Iterator<Item = crate::Result<Entry<'i>>> + 'i
+    where
+        Self: 'i;
+
+    /// The prefix iterator type yielding just the keys
+    type PrefixKeyIter<'i>: Iterator<Item = crate::Result<Cow<'i, [u8]>>> + 'i
+    where
+        Self: 'i;
+
+    /// The range iterator type
+    type RangeIter<'i>: Iterator<Item = crate::Result<Entry<'i>>> + 'i
+    where
+        Self: 'i;
+
+    /// The reverse range iterator type
+    type RangeIterRev<'i>: Iterator<Item = crate::Result<Entry<'i>>> + 'i
+    where
+        Self: 'i;
+
+    /// The cursor type
+    type Cursor<'i>: Cursor + 'i
     where
         Self: 'i;
 
@@ -33,6 +59,70 @@ pub trait ReadOps {
 
     /// Get iterator over key-value pairs where the key has given prefix
     fn prefix_iter(&self, map_id: DbMapId, prefix: Data) -> crate::Result<Self::PrefixIter<'_>>;
+
+    /// Get iterator over keys with given prefix. The values are not retrieved at all.
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> crate::Result<Self::PrefixKeyIter<'_>>;
+
+    /// Get iterator over key-value pairs where the key lies between given bounds, in ascending
+    /// key order
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIter<'_>>;
+
+    /// Get iterator over key-value pairs where the key lies between given bounds, in descending
+    /// key order
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<Self::RangeIterRev<'_>>;
+
+    /// Get iterator over key-value pairs where the key has given prefix, in descending key order
+    fn prefix_iter_rev(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> crate::Result<Self::RangeIterRev<'_>> {
+        let (start, end) = crate::util::prefix_range(prefix);
+        self.range_iter_rev(map_id, start, end)
+    }
+
+    /// Get a cursor over key-value pairs in given map. The cursor is initially unpositioned.
+    fn cursor(&self, map_id: DbMapId) -> crate::Result<Self::Cursor<'_>>;
+}
+
+/// Cursor over the key-value pairs of a map, movable in both directions.
+///
+/// The cursor is either positioned at an entry or unpositioned. Each operation returns the entry
+/// the cursor ends up at. Operations that do not find an entry leave the cursor unpositioned.
+/// Moving an unpositioned cursor forward with [Cursor::next] gives the first entry and moving it
+/// backward with [Cursor::prev] gives the last entry.
+pub trait Cursor {
+    /// Move to the first entry with key greater than or equal to given key
+    fn seek(&mut self, key: &[u8]) -> crate::Result<Option<(Data, Data)>>;
+
+    /// Move to the first entry
+    fn first(&mut self) -> crate::Result<Option<(Data, Data)>>;
+
+    /// Move to the last entry
+    fn last(&mut self) -> crate::Result<Option<(Data, Data)>>;
+
+    /// Move to the next entry
+    fn next(&mut self) -> crate::Result<Option<(Data, Data)>>;
+
+    /// Move to the previous entry
+    fn prev(&mut self) -> crate::Result<Option<(Data, Data)>>;
+
+    /// Get the entry the cursor is currently positioned at
+    fn current(&self) -> crate::Result<Option<(Data, Data)>>;
 }
 
 /// Write database operation
@@ -42,6 +132,45 @@ pub trait WriteOps {
 
     /// Delete the value associated with given key.
     fn del(&mut self, map_id: DbMapId, key: &[u8]) -> crate::Result<()>;
+
+    /// Delete the values associated with all keys that lie between given bounds.
+    fn del_range(
+        &mut self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> crate::Result<()>;
+
+    /// Delete the values associated with all keys that start with given prefix.
+    fn del_prefix(&mut self, map_id: DbMapId, prefix: Data) -> crate::Result<()> {
+        let (start, end) = crate::util::prefix_range(prefix);
+        self.del_range(map_id, start, end)
+    }
+
+    /// Delete all values in given map.
+    fn clear(&mut self, map_id: DbMapId) -> crate::Result<()> {
+        self.del_range(map_id, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Set value associated with given key only if there is no value associated with it yet.
+    /// Returns whether the value has been set.
+    fn put_if_absent(&mut self, map_id: DbMapId, key: Data, val: Data) -> crate::Result<bool> {
+        self.compare_and_swap(map_id, key, None, Some(val))
+    }
+
+    /// Replace the value associated with given key only if the current value equals `expected`.
+    /// Returns whether the value has been replaced.
+    ///
+    /// A `None` in `expected` stands for no value being associated with the key. A `None` in `new`
+    /// deletes the value. See [crate::DbMapDesc::with_duplicate_values] for maps where a key may
+    /// hold multiple values.
+    fn compare_and_swap(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> crate::Result<bool>;
 }
 
 /// Read-only transaction
@@ -52,9 +181,23 @@ pub trait TxRo: ReadOps {}
 /// Read-write transaction
 ///
 /// If a cleanup is required when the transaction closes, [Drop] should be implemented too.
+///
+/// Savepoints form a stack. [TxRw::rollback_to] and [TxRw::release] operate on the most recently
+/// created savepoint that is still active and fail with [crate::error::Fatal::NoSavepoint] if
+/// there is none. Committing the transaction keeps all changes, including those made since any
+/// savepoints that are still active.
 pub trait TxRw: ReadOps + WriteOps {
     /// Commit changes from this transaction
     fn commit(self) -> crate::Result<()>;
+
+    /// Create a savepoint capturing the current state of the transaction
+    fn savepoint(&mut self) -> crate::Result<()>;
+
+    /// Discard the changes made since the most recent savepoint and remove the savepoint
+    fn rollback_to(&mut self) -> crate::Result<()>;
+
+    /// Remove the most recent savepoint, keeping the changes made since it was created
+    fn release(&mut self) -> crate::Result<()>;
 }
 
 /// Storage backend internal implementation type
@@ -79,4 +222,31 @@ pub trait Backend {
 
     /// Open the database, giving an implementation-specific handle
     fn open(self, desc: DbDesc) -> crate::Result<Self::Impl>;
+
+    /// Open the database like [Backend::open], first passing the current contents of the map
+    /// with given index to `check`, empty if the map does not exist yet. If the check fails,
+    /// none of the maps that do not exist yet are created.
+    ///
+    /// The default implementation runs the check after opening the database, which is only
+    /// suitable for backends where opening leaves nothing behind, like in-memory ones.
+    fn open_checked(
+        self,
+        desc: DbDesc,
+        checked_map: DbMapId,
+        check: impl FnOnce(&BTreeMap<Data, Data>) -> crate::Result<()>,
+    ) -> crate::Result<Self::Impl>
+    where
+        Self: Sized,
+    {
+        let backend = self.open(desc)?;
+        {
+            let dbtx = backend.transaction_ro()?;
+            let contents: crate::Result<BTreeMap<Data, Data>> = dbtx
+                .prefix_iter(checked_map, Data::new())?
+                .map(|entry| entry.map(|(k, v)| (k.into_owned(), v.into_owned())))
+                .collect();
+            check(&contents?)?;
+        }
+        Ok(backend)
+    }
 }
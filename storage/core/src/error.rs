@@ -46,8 +46,16 @@ pub enum Fatal {
     DatabaseCorrupted,
     #[error("Database internal error: {0}")]
     InternalError(String),
-    #[error("Database schema does not match database settings or contents")]
-    SchemaMismatch,
+    #[error("Database schema does not match database settings or contents: {0}")]
+    SchemaMismatch(String),
+    #[error("Database schema version {0} is newer than the latest supported version {1}")]
+    SchemaVersionTooNew(u32, u32),
+    #[error("No savepoint to roll back to or release")]
+    NoSavepoint,
+    #[error("Operation not supported: {0}")]
+    UnsupportedOperation(String),
+    #[error("Invalid database options: {0}")]
+    InvalidOptions(String),
     #[error("Fatal I/O error: {1}")]
     Io(std::io::ErrorKind, String),
 }
@@ -110,6 +110,23 @@ impl<T> std::ops::IndexMut<DbMapId> for DbMapsData<T> {
     }
 }
 
+/// How keys in a key-value map are ordered.
+///
+/// Range bounds are interpreted in the key order of the map. Keys sharing a prefix are only
+/// adjacent in the lexicographic order, so maps with other orders do not support selecting
+/// entries by a non-empty prefix.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub enum KeyOrder {
+    /// Lexicographic order of the key bytes
+    #[default]
+    Lexicographic,
+    /// Lexicographic order of the key bytes read from the last one to the first one
+    Reverse,
+    /// Numeric order of unsigned integers in the native byte order. The keys all have to be
+    /// either 4 or 8 bytes long.
+    Integer,
+}
+
 /// Description of one key-value store in a database
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct DbMapDesc {
@@ -117,6 +134,10 @@ pub struct DbMapDesc {
     name: String,
     /// Value size hint
     value_size_hint: Range<usize>,
+    /// Key order
+    key_order: KeyOrder,
+    /// Whether each key can hold multiple values
+    duplicate_values: bool,
 }
 
 impl DbMapDesc {
@@ -130,9 +151,29 @@ impl DbMapDesc {
         Self {
             name: name.into(),
             value_size_hint,
+            key_order: KeyOrder::Lexicographic,
+            duplicate_values: false,
         }
     }
 
+    /// Order the keys in given way
+    pub fn with_key_order(mut self, key_order: KeyOrder) -> Self {
+        self.key_order = key_order;
+        self
+    }
+
+    /// Allow each key to hold multiple values, kept sorted.
+    ///
+    /// Writing a value to a key then adds it to the values already there, reading the key gives
+    /// the smallest value and deleting the key removes all of its values. A conditional write
+    /// succeeds if the expected value is among the values of the key and replaces only that
+    /// one, or deletes it if there is no new value. Iteration visits each value separately. If
+    /// the value size hint allows a single size only, the values are assumed to be of that size.
+    pub fn with_duplicate_values(mut self) -> Self {
+        self.duplicate_values = true;
+        self
+    }
+
     /// Get DB map name
     pub fn name(&self) -> &str {
         &self.name
@@ -142,6 +183,21 @@ impl DbMapDesc {
     pub fn value_size_hint(&self) -> &Range<usize> {
         &self.value_size_hint
     }
+
+    /// Get key order
+    pub fn key_order(&self) -> KeyOrder {
+        self.key_order
+    }
+
+    /// Whether each key can hold multiple values
+    pub fn duplicate_values(&self) -> bool {
+        self.duplicate_values
+    }
+
+    /// Whether the map orders keys lexicographically and keeps a single value per key
+    pub fn is_plain(&self) -> bool {
+        self.key_order == KeyOrder::Lexicographic && !self.duplicate_values
+    }
 }
 
 /// Metadata about the whole database
@@ -15,8 +15,24 @@
 
 //! Utilities for implementing storage backends
 
-use crate::Data;
-use std::collections::BTreeMap;
+use crate::{error::Fatal, Data, DbDesc};
+use std::{collections::BTreeMap, ops::Bound};
+
+/// Reject maps that order keys other than lexicographically or hold multiple values per key, for
+/// backends that do not support them
+pub fn ensure_plain_maps(desc: &DbDesc) -> crate::Result<()> {
+    for idx in desc.db_map_count().indices() {
+        let map_desc = &desc.db_maps()[idx];
+        utils::ensure!(
+            map_desc.is_plain(),
+            Fatal::SchemaMismatch(format!(
+                "Map {} needs a key order or duplicate values not supported by the backend",
+                map_desc.name()
+            ))
+        );
+    }
+    Ok(())
+}
 
 /// Iterator over entries of a [BTreeMap] with keys starting with given prefix
 pub struct PrefixIter<'m, T> {
@@ -40,3 +56,151 @@ impl<'m, T> Iterator for PrefixIter<'m, T> {
             .and_then(|(k, v)| k.starts_with(&self.prefix[..]).then(|| (k.as_ref(), v)))
     }
 }
+
+/// Iterator adaptor that ends the iteration once the underlying iterator yields an error
+pub struct EndOnError<I>(Option<I>);
+
+impl<I> EndOnError<I> {
+    pub fn new(inner: I) -> Self {
+        Self(Some(inner))
+    }
+}
+
+impl<T, E, I: Iterator<Item = Result<T, E>>> Iterator for EndOnError<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.0.as_mut()?.next();
+        if !matches!(item, Some(Ok(_))) {
+            self.0 = None;
+        }
+        item
+    }
+}
+
+/// Get the range of keys starting with given prefix, expressed as a pair of bounds
+pub fn prefix_range(prefix: Data) -> (Bound<Data>, Bound<Data>) {
+    // The upper bound is obtained by stripping all trailing 0xff bytes and incrementing the last
+    // byte that remains. If there is no such byte, the range is unbounded from above.
+    let mut end = prefix.clone();
+    while end.last() == Some(&0xff) {
+        end.pop();
+    }
+    let end = match end.last_mut() {
+        Some(last) => {
+            *last += 1;
+            Bound::Excluded(end)
+        }
+        None => Bound::Unbounded,
+    };
+    (Bound::Included(prefix), end)
+}
+
+/// Check whether given key lies past the end bound of a range
+pub fn is_past_end(end: &Bound<Data>, key: &[u8]) -> bool {
+    match end {
+        Bound::Included(end) => key > end.as_slice(),
+        Bound::Excluded(end) => key >= end.as_slice(),
+        Bound::Unbounded => false,
+    }
+}
+
+/// Check whether given key lies before the start bound of a range
+pub fn is_before_start(start: &Bound<Data>, key: &[u8]) -> bool {
+    match start {
+        Bound::Included(start) => key < start.as_slice(),
+        Bound::Excluded(start) => key <= start.as_slice(),
+        Bound::Unbounded => false,
+    }
+}
+
+/// Check whether the range given by the two bounds contains no keys at all
+pub fn is_empty_range(start: &Bound<Data>, end: &Bound<Data>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e)) => s >= e,
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => false,
+    }
+}
+
+/// Iterator over entries of a [BTreeMap] with keys within given range
+pub struct RangeIter<'m, T> {
+    inner: Option<std::collections::btree_map::Range<'m, Data, T>>,
+}
+
+impl<'m, T> RangeIter<'m, T> {
+    pub fn new(map: &'m BTreeMap<Data, T>, start: Bound<Data>, end: Bound<Data>) -> Self {
+        // The BTreeMap::range method panics on empty ranges, so these are handled separately
+        let inner = (!is_empty_range(&start, &end)).then(|| map.range((start, end)));
+        Self { inner }
+    }
+}
+
+impl<'m, T> Iterator for RangeIter<'m, T> {
+    type Item = (&'m [u8], &'m T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next().map(|(k, v)| (k.as_ref(), v))
+    }
+}
+
+impl<'m, T> DoubleEndedIterator for RangeIter<'m, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next_back().map(|(k, v)| (k.as_ref(), v))
+    }
+}
+
+/// Cursor over entries of a [BTreeMap], following the semantics of [crate::backend::Cursor]
+pub struct MapCursor<'m, T> {
+    map: &'m BTreeMap<Data, T>,
+    current: Option<(&'m Data, &'m T)>,
+}
+
+impl<'m, T> MapCursor<'m, T> {
+    pub fn new(map: &'m BTreeMap<Data, T>) -> Self {
+        let current = None;
+        Self { map, current }
+    }
+
+    fn set(&mut self, current: Option<(&'m Data, &'m T)>) -> Option<(&'m [u8], &'m T)> {
+        self.current = current;
+        self.current()
+    }
+
+    pub fn current(&self) -> Option<(&'m [u8], &'m T)> {
+        self.current.map(|(k, v)| (k.as_slice(), v))
+    }
+
+    pub fn seek(&mut self, key: &[u8]) -> Option<(&'m [u8], &'m T)> {
+        let range = (Bound::Included(key), Bound::Unbounded);
+        self.set(self.map.range::<[u8], _>(range).next())
+    }
+
+    pub fn first(&mut self) -> Option<(&'m [u8], &'m T)> {
+        self.set(self.map.iter().next())
+    }
+
+    pub fn last(&mut self) -> Option<(&'m [u8], &'m T)> {
+        self.set(self.map.iter().next_back())
+    }
+
+    // Mirrors backend::Cursor::next, not meant to be an Iterator
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&'m [u8], &'m T)> {
+        match self.current {
+            Some((key, _)) => {
+                let range = (Bound::Excluded(key), Bound::Unbounded);
+                self.set(self.map.range::<Data, _>(range).next())
+            }
+            None => self.first(),
+        }
+    }
+
+    pub fn prev(&mut self) -> Option<(&'m [u8], &'m T)> {
+        match self.current {
+            Some((key, _)) => self.set(self.map.range::<Data, _>(..key).next_back()),
+            None => self.last(),
+        }
+    }
+}
@@ -15,17 +15,80 @@
 
 use storage_core::{adaptor, backend, util, Data, DbDesc, DbMapId, DbMapsData};
 
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, ops::Bound};
 
 type Map = BTreeMap<Data, Data>;
 
+/// Borrow an entry from the map
+fn borrowed_entry<'i>((k, v): (&'i [u8], &'i Data)) -> backend::Entry<'i> {
+    (k.into(), v.as_slice().into())
+}
+
 pub struct PrefixIter<'i>(util::PrefixIter<'i, Data>);
 
 impl<'i> Iterator for PrefixIter<'i> {
-    type Item = (Data, Data);
+    type Item = storage_core::Result<backend::Entry<'i>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(borrowed_entry).map(Ok)
+    }
+}
+
+pub struct PrefixKeyIter<'i>(util::PrefixIter<'i, Data>);
+
+impl<'i> Iterator for PrefixKeyIter<'i> {
+    type Item = storage_core::Result<Cow<'i, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next().map(|(k, v)| (k.to_vec(), v.clone()))
+        self.0.next().map(|(k, _)| Ok(k.into()))
+    }
+}
+
+pub struct RangeIter<'i>(util::RangeIter<'i, Data>);
+
+impl<'i> Iterator for RangeIter<'i> {
+    type Item = storage_core::Result<backend::Entry<'i>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(borrowed_entry).map(Ok)
+    }
+}
+
+impl<'i> DoubleEndedIterator for RangeIter<'i> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(borrowed_entry).map(Ok)
+    }
+}
+
+pub struct Cursor<'i>(util::MapCursor<'i, Data>);
+
+fn cloned_entry((k, v): (&[u8], &Data)) -> (Data, Data) {
+    (k.to_vec(), v.clone())
+}
+
+impl backend::Cursor for Cursor<'_> {
+    fn seek(&mut self, key: &[u8]) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.seek(key).map(cloned_entry))
+    }
+
+    fn first(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.first().map(cloned_entry))
+    }
+
+    fn last(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.last().map(cloned_entry))
+    }
+
+    fn next(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.next().map(cloned_entry))
+    }
+
+    fn prev(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.prev().map(cloned_entry))
+    }
+
+    fn current(&self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.0.current().map(cloned_entry))
     }
 }
 
@@ -33,6 +96,10 @@ pub struct StorageMaps(DbMapsData<Map>);
 
 impl backend::ReadOps for StorageMaps {
     type PrefixIter<'i> = PrefixIter<'i>;
+    type PrefixKeyIter<'i> = PrefixKeyIter<'i>;
+    type RangeIter<'i> = RangeIter<'i>;
+    type RangeIterRev<'i> = std::iter::Rev<RangeIter<'i>>;
+    type Cursor<'i> = Cursor<'i>;
 
     fn get(&self, map_id: DbMapId, key: &[u8]) -> storage_core::Result<Option<Cow<[u8]>>> {
         Ok(self.0[map_id].get(key).map(|p| p.into()))
@@ -45,6 +112,37 @@ impl backend::ReadOps for StorageMaps {
     ) -> storage_core::Result<Self::PrefixIter<'_>> {
         Ok(PrefixIter(util::PrefixIter::new(&self.0[map_id], prefix)))
     }
+
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> storage_core::Result<Self::PrefixKeyIter<'_>> {
+        let iter = util::PrefixIter::new(&self.0[map_id], prefix);
+        Ok(PrefixKeyIter(iter))
+    }
+
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIter<'_>> {
+        Ok(RangeIter(util::RangeIter::new(&self.0[map_id], start, end)))
+    }
+
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIterRev<'_>> {
+        self.range_iter(map_id, start, end).map(Iterator::rev)
+    }
+
+    fn cursor(&self, map_id: DbMapId) -> storage_core::Result<Self::Cursor<'_>> {
+        Ok(Cursor(util::MapCursor::new(&self.0[map_id])))
+    }
 }
 
 impl backend::WriteOps for StorageMaps {
@@ -57,12 +155,51 @@ impl backend::WriteOps for StorageMaps {
         let _ = self.0[map_id].remove(key);
         Ok(())
     }
+
+    fn del_range(
+        &mut self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<()> {
+        let map = &mut self.0[map_id];
+        let keys: Vec<Data> =
+            util::RangeIter::new(map, start, end).map(|(k, _)| k.to_vec()).collect();
+        keys.iter().for_each(|k| {
+            let _ = map.remove(k);
+        });
+        Ok(())
+    }
+
+    fn clear(&mut self, map_id: DbMapId) -> storage_core::Result<()> {
+        self.0[map_id].clear();
+        Ok(())
+    }
+
+    fn compare_and_swap(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> storage_core::Result<bool> {
+        let map = &mut self.0[map_id];
+        let matches = map.get(&key).map(Vec::as_slice) == expected;
+        if matches {
+            let _ = match new {
+                Some(val) => map.insert(key, val),
+                None => map.remove(&key),
+            };
+        }
+        Ok(matches)
+    }
 }
 
 impl adaptor::Construct for StorageMaps {
     type From = ();
 
     fn construct(_: (), desc: DbDesc) -> storage_core::Result<Self> {
+        storage_core::util::ensure_plain_maps(&desc)?;
         Ok(Self(desc.db_maps().transform(|_| Map::new())))
     }
 }
@@ -0,0 +1,77 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the change tracking in transactions
+
+use std::ops::Bound;
+
+use storage_core::{
+    backend::{Backend, BackendImpl, ReadOps, TxRw, WriteOps},
+    DbMapDesc, DbMapId,
+};
+use storage_inmemory::InMemory;
+
+const MAP: DbMapId = DbMapId::new(0);
+const NUM_ENTRIES: u32 = 1000;
+
+fn populated() -> <InMemory as Backend>::Impl {
+    let desc = storage_core::types::construct::db_desc([DbMapDesc::new("map")].into_iter());
+    let store = InMemory::new().open(desc).unwrap();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for i in 0..NUM_ENTRIES {
+        dbtx.put(MAP, i.to_be_bytes().to_vec(), vec![1]).unwrap();
+    }
+    dbtx.commit().unwrap();
+    store
+}
+
+#[test]
+fn clear_tracked_compactly() {
+    let store = populated();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAP, vec![0xff], vec![2]).unwrap();
+    dbtx.clear(MAP).unwrap();
+    assert_eq!(dbtx.num_tracked_changes(), 1);
+
+    // Clearing the map again or deleting ranges in it does not add anything
+    dbtx.clear(MAP).unwrap();
+    dbtx.del_range(MAP, Bound::Unbounded, Bound::Excluded(vec![0x80])).unwrap();
+    assert_eq!(dbtx.num_tracked_changes(), 1);
+    assert_eq!(dbtx.prefix_iter_keys(MAP, vec![]).unwrap().count(), 0);
+
+    dbtx.put(MAP, vec![0xff], vec![3]).unwrap();
+    assert_eq!(dbtx.num_tracked_changes(), 2);
+    dbtx.commit().unwrap();
+
+    let dbtx = store.transaction_ro().unwrap();
+    let contents: Vec<_> = dbtx.prefix_iter(MAP, vec![]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(contents, [(vec![0xff].into(), vec![3].into())]);
+}
+
+#[test]
+fn del_range_tracked_compactly() {
+    let store = populated();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    let end = (NUM_ENTRIES / 2).to_be_bytes().to_vec();
+    dbtx.del_range(MAP, Bound::Unbounded, Bound::Excluded(end)).unwrap();
+    assert_eq!(dbtx.num_tracked_changes(), 1);
+    dbtx.commit().unwrap();
+
+    let dbtx = store.transaction_ro().unwrap();
+    assert_eq!(
+        dbtx.prefix_iter_keys(MAP, vec![]).unwrap().count(),
+        (NUM_ENTRIES / 2) as usize
+    );
+}
@@ -0,0 +1,139 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Copying and compacting the database
+
+use std::ffi::CString;
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error;
+
+/// Name of the data file in the database directory
+const DATA_FILE_NAME: &str = "data.mdb";
+
+/// Suffix of the temporary copy made when compacting the database in place
+const COMPACT_SUFFIX: &str = "compact";
+
+/// Sizes of the data file before and after copying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyStats {
+    /// Size of the original data file in bytes
+    pub size_before: u64,
+    /// Size of the copy in bytes
+    pub size_after: u64,
+}
+
+/// Location of the data file of a database stored at given path
+pub(crate) fn data_file(path: &Path, no_sub_dir: bool) -> PathBuf {
+    if no_sub_dir {
+        path.to_owned()
+    } else {
+        path.join(DATA_FILE_NAME)
+    }
+}
+
+/// Location of the temporary copy made when compacting the database in place. It is a
+/// directory unless the data is stored directly in a file.
+pub(crate) fn compact_target(path: &Path, no_sub_dir: bool) -> PathBuf {
+    if no_sub_dir {
+        let mut target = path.as_os_str().to_owned();
+        target.push(".");
+        target.push(COMPACT_SUFFIX);
+        target.into()
+    } else {
+        path.join(COMPACT_SUFFIX)
+    }
+}
+
+#[cfg(unix)]
+fn path_to_cstring(path: &Path) -> Option<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+#[cfg(not(unix))]
+fn path_to_cstring(path: &Path) -> Option<CString> {
+    path.to_str().and_then(|path| CString::new(path).ok())
+}
+
+fn file_size(path: &Path) -> storage_core::Result<u64> {
+    Ok(std::fs::metadata(path).map_err(error::process_io_error)?.len())
+}
+
+/// Copy the database into `target`, which is either a directory that must not contain a data
+/// file yet or, if the data is stored directly in a file, the path to the new file.
+pub(crate) fn copy(
+    env: &lmdb::Environment,
+    data_file_path: &Path,
+    target: &Path,
+    no_sub_dir: bool,
+    compact: bool,
+) -> storage_core::Result<CopyStats> {
+    let target_dir = if no_sub_dir {
+        target.parent()
+    } else {
+        Some(target)
+    };
+    if let Some(dir) = target_dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(error::process_io_error)?;
+    }
+
+    let c_target = path_to_cstring(target).ok_or_else(|| {
+        let err = IoError::new(ErrorKind::InvalidInput, "Unsupported backup path");
+        error::process_io_error(err)
+    })?;
+    let flags = if compact { lmdb_sys::MDB_CP_COMPACT } else { 0 };
+
+    let size_before = file_size(data_file_path)?;
+    // SAFETY: The environment is open for as long as it is borrowed and the path is a valid
+    // NUL-terminated string that outlives the call.
+    let rc = unsafe { lmdb_sys::mdb_env_copy2(env.env(), c_target.as_ptr(), flags) };
+    if rc != 0 {
+        return error::process_with_err(lmdb::Error::from_err_code(rc));
+    }
+    let size_after = file_size(&data_file(target, no_sub_dir))?;
+
+    Ok(CopyStats {
+        size_before,
+        size_after,
+    })
+}
+
+/// Replace the data file with its compacted copy made by [copy] into [compact_target]
+pub(crate) fn swap_in_compacted(path: &Path, no_sub_dir: bool) -> storage_core::Result<()> {
+    let target = compact_target(path, no_sub_dir);
+    let compacted = data_file(&target, no_sub_dir);
+    // Renaming within a directory atomically replaces the original file
+    std::fs::rename(compacted, data_file(path, no_sub_dir)).map_err(error::process_io_error)?;
+    if !no_sub_dir {
+        std::fs::remove_dir(target).map_err(error::process_io_error)?;
+    }
+    Ok(())
+}
+
+/// Remove any temporary copy left behind by an interrupted compaction
+pub(crate) fn remove_compact_target(path: &Path, no_sub_dir: bool) -> storage_core::Result<()> {
+    let target = compact_target(path, no_sub_dir);
+    let result = if no_sub_dir {
+        std::fs::remove_file(target)
+    } else {
+        std::fs::remove_dir_all(target)
+    };
+    match result {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(error::process_io_error(err)),
+        _ => Ok(()),
+    }
+}
@@ -32,8 +32,9 @@ fn process<T>(err: Error, default: storage_core::Result<T>) -> storage_core::Res
         // Transaction failed to commit
         Error::BadTxn => Err(Recoverable::TransactionFailed.into()),
 
-        // We have exhausted some resource which may become available again later
-        Error::ReadersFull | Error::TxnFull | Error::TlsFull => {
+        // We have exhausted some resource which may become available again later. The map is
+        // resized automatically once it is full so the transaction can be retried.
+        Error::ReadersFull | Error::TxnFull | Error::TlsFull | Error::MapFull => {
             Err(Recoverable::TemporarilyUnavailable.into())
         }
 
@@ -43,12 +44,11 @@ fn process<T>(err: Error, default: storage_core::Result<T>) -> storage_core::Res
         | Error::CursorFull
         | Error::PageFull
         | Error::BadRslot
-        | Error::MapFull
         | Error::MapResized) => Err(Fatal::InternalError(err.to_string()).into()),
 
         // These signify the database flags are not in sync with the schema
-        Error::DbsFull | Error::BadValSize | Error::Incompatible => {
-            Err(Fatal::SchemaMismatch.into())
+        err @ (Error::DbsFull | Error::BadValSize | Error::Incompatible) => {
+            Err(Fatal::SchemaMismatch(err.to_string()).into())
         }
 
         // These are database corruption issues
@@ -90,6 +90,38 @@ pub fn process_with_none<T>(err: Error) -> storage_core::Result<Option<T>> {
     process(err, Ok(None))
 }
 
+/// Error codes of running out of disk space
+#[cfg(windows)]
+pub const OUT_OF_SPACE_CODES: [i32; 2] = [39, 112]; // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+#[cfg(not(windows))]
+pub const OUT_OF_SPACE_CODES: [i32; 1] = [28]; // ENOSPC
+
+/// Error codes of a database map resize failing only until other users of the map let go of it,
+/// in addition to `EAGAIN` whose value differs between platforms
+#[cfg(windows)]
+pub const TRANSIENT_RESIZE_CODES: [i32; 2] = [32, 33]; // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+#[cfg(not(windows))]
+pub const TRANSIENT_RESIZE_CODES: [i32; 1] = [16]; // EBUSY
+
+/// Whether a failed database map resize is worth attempting again
+pub fn is_transient_resize_error(err: &Error) -> bool {
+    match err {
+        Error::Other(errno) => {
+            TRANSIENT_RESIZE_CODES.contains(errno)
+                || IoError::from_raw_os_error(*errno).kind() == ErrorKind::WouldBlock
+        }
+        _ => false,
+    }
+}
+
+/// Process an error of a failed database map resize, telling apart running out of disk space
+pub fn process_resize_error<T>(err: Error) -> storage_core::Result<T> {
+    match err {
+        Error::Other(errno) if OUT_OF_SPACE_CODES.contains(&errno) => Err(Fatal::OutOfSpace.into()),
+        err => process_with_err(err),
+    }
+}
+
 /// Process an error with operations where "successful" error codes are not expected
 pub fn process_with_err<T>(err: Error) -> storage_core::Result<T> {
     process(err, Err(Fatal::InternalError(err.to_string()).into()))
@@ -13,9 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::memsize::MemSize;
+use std::str::FromStr;
 
+use crate::memsize::{MemSize, MemSizeError};
+
+/// Serialized as the memory size, if any, see [MemSize]
 #[derive(Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct InitialMapSize {
     initial_map_size: Option<MemSize>,
 }
@@ -39,3 +47,12 @@ impl From<InitialMapSize> for Option<MemSize> {
         initial_map_size.initial_map_size
     }
 }
+
+/// Parses the memory size as [MemSize] does
+impl FromStr for InitialMapSize {
+    type Err = MemSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<MemSize>().map(Self::from)
+    }
+}
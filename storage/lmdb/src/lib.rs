@@ -16,30 +16,41 @@
 #[cfg(not(target_pointer_width = "64"))]
 compile_error!("LMDB only compiles for 64-bit targets due to the way memory mapping works");
 
+pub mod backup;
 mod error;
 pub mod initial_map_size;
 pub mod memsize;
+mod readers;
 pub mod resize_callback;
+pub mod resize_settings;
+pub mod stats;
 
 // Note: we can't use utils::sync::atomic types here at the moment, because certain tests,
 // when run with loom, will panic with the message "Model exceeded maximum number of branches".
 // Probably we just need to configure loom model with a bigger max_branches value?
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::{borrow::Cow, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::Bound,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use initial_map_size::InitialMapSize;
 use lmdb::Cursor;
 use resize_callback::MapResizeCallback;
-use storage_core::{backend, Data, DbDesc, DbMapDesc, DbMapId, DbMapsData};
+use storage_core::{backend, util, Data, DbDesc, DbMapDesc, DbMapId, DbMapsData};
 use utils::const_value::ConstValue;
 use utils::sync::Arc;
 
-pub use lmdb::{DatabaseResizeInfo, DatabaseResizeSettings};
+pub use lmdb::DatabaseResizeSettings;
+pub use resize_callback::DatabaseResizeInfo;
 
 /// LMDB iterator over entries with given key prefix
 pub struct PrefixIter<'tx, C> {
     /// Underlying iterator
-    iter: lmdb::Iter<'tx, C>,
+    iter: util::EndOnError<lmdb::Iter<'tx, C>>,
 
     /// Prefix to iterate over
     prefix: Data,
@@ -47,33 +58,414 @@ pub struct PrefixIter<'tx, C> {
 
 impl<'tx, C> PrefixIter<'tx, C> {
     fn new(iter: lmdb::Iter<'tx, C>, prefix: Data) -> Self {
+        let iter = util::EndOnError::new(iter);
         PrefixIter { iter, prefix }
     }
 }
 
+impl<'tx, C: Cursor<'tx>> PrefixIter<'tx, C> {
+    /// Next entry, borrowed from the database
+    fn next_ref(&mut self) -> Option<storage_core::Result<(&'tx [u8], &'tx [u8])>> {
+        let (k, v) = match self.iter.next()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(error::process_with_err(err)),
+        };
+        utils::ensure!(k.starts_with(&self.prefix));
+        Some(Ok((k, v)))
+    }
+}
+
 impl<'tx, C: Cursor<'tx>> Iterator for PrefixIter<'tx, C> {
-    type Item = (Data, Data);
+    type Item = storage_core::Result<backend::Entry<'tx>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (k, v) = self.iter.next()?.expect("iteration to proceed");
-        utils::ensure!(k.starts_with(&self.prefix));
-        Some((k.to_vec(), v.to_vec()))
+        self.next_ref().map(|res| res.map(|(k, v)| (k.into(), v.into())))
+    }
+}
+
+/// LMDB iterator over keys with given prefix
+pub struct PrefixKeyIter<'tx, C>(PrefixIter<'tx, C>);
+
+impl<'tx, C: Cursor<'tx>> Iterator for PrefixKeyIter<'tx, C> {
+    type Item = storage_core::Result<Cow<'tx, [u8]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_ref().map(|res| res.map(|(k, _)| k.into()))
+    }
+}
+
+/// Key comparison in the order of a particular database, which is not necessarily lexicographic,
+/// see [storage_core::KeyOrder]. It must only be used while the transaction it has been created
+/// from is alive.
+#[derive(Clone, Copy)]
+struct KeyCmp {
+    txn: *mut lmdb_sys::MDB_txn,
+    dbi: lmdb_sys::MDB_dbi,
+}
+
+impl KeyCmp {
+    fn new(tx: &impl lmdb::Transaction, db: lmdb::Database) -> Self {
+        KeyCmp {
+            txn: tx.txn(),
+            dbi: db.dbi(),
+        }
+    }
+
+    /// Compare a key from the database with a key given by the user
+    fn cmp(&self, key: &[u8], other: &[u8]) -> std::cmp::Ordering {
+        let val = |data: &[u8]| lmdb_sys::MDB_val {
+            mv_size: data.len(),
+            mv_data: data.as_ptr() as *mut std::ffi::c_void,
+        };
+        let (key, other) = (val(key), val(other));
+        // SAFETY: The transaction is alive and the values point to valid slices.
+        // Integer keys are read according to the size of the first one, which comes from the
+        // database, and the user provided keys have been checked to have a valid size.
+        let res = unsafe { lmdb_sys::mdb_cmp(self.txn, self.dbi, &key, &other) };
+        res.cmp(&0)
+    }
+
+    /// Like [util::is_past_end] but in the database order
+    fn is_past_end(&self, end: &Bound<Data>, key: &[u8]) -> bool {
+        match end {
+            Bound::Included(end) => self.cmp(key, end).is_gt(),
+            Bound::Excluded(end) => self.cmp(key, end).is_ge(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    /// Like [util::is_before_start] but in the database order
+    fn is_before_start(&self, start: &Bound<Data>, key: &[u8]) -> bool {
+        match start {
+            Bound::Included(start) => self.cmp(key, start).is_lt(),
+            Bound::Excluded(start) => self.cmp(key, start).is_le(),
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+/// LMDB iterator over entries with keys in given range
+pub struct RangeIter<'tx, C> {
+    /// Underlying iterator, positioned at the start bound
+    iter: util::EndOnError<lmdb::Iter<'tx, C>>,
+
+    /// Start of the range, used to skip the excluded start key
+    start: Bound<Data>,
+
+    /// End of the range
+    end: Bound<Data>,
+
+    /// Key order of the database
+    key_cmp: KeyCmp,
+}
+
+impl<'tx, C> RangeIter<'tx, C> {
+    fn new(
+        iter: lmdb::Iter<'tx, C>,
+        start: Bound<Data>,
+        end: Bound<Data>,
+        key_cmp: KeyCmp,
+    ) -> Self {
+        let iter = util::EndOnError::new(iter);
+        RangeIter {
+            iter,
+            start,
+            end,
+            key_cmp,
+        }
+    }
+}
+
+impl<'tx, C: Cursor<'tx>> Iterator for RangeIter<'tx, C> {
+    type Item = storage_core::Result<backend::Entry<'tx>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (k, v) = match self.iter.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(error::process_with_err(err)),
+            };
+            if self.key_cmp.is_before_start(&self.start, k) {
+                continue;
+            }
+            utils::ensure!(!self.key_cmp.is_past_end(&self.end, k));
+            return Some(Ok((k.into(), v.into())));
+        }
+    }
+}
+
+/// Raw LMDB cursor operations (`MDB_cursor_op`) not covered by the high-level iterators
+mod cursor_op {
+    use std::ffi::c_uint;
+
+    pub const FIRST: c_uint = 0;
+    pub const LAST: c_uint = 6;
+    pub const LAST_DUP: c_uint = 7;
+    pub const NEXT: c_uint = 8;
+    pub const PREV: c_uint = 12;
+    pub const SET_RANGE: c_uint = 17;
+}
+
+/// LMDB iterator over entries with keys in given range, in descending key order
+pub struct RangeIterRev<'tx, C> {
+    /// Underlying cursor
+    cursor: C,
+
+    /// Whether the cursor has been positioned at the end bound yet
+    positioned: bool,
+
+    /// Whether the iteration has been cut short by an error
+    failed: bool,
+
+    /// Start of the range
+    start: Bound<Data>,
+
+    /// End of the range, used to position the cursor initially
+    end: Bound<Data>,
+
+    /// Key order of the database
+    key_cmp: KeyCmp,
+
+    /// Whether the database allows multiple values per key
+    duplicate_values: bool,
+
+    _phantom: std::marker::PhantomData<&'tx ()>,
+}
+
+impl<'tx, C: Cursor<'tx>> RangeIterRev<'tx, C> {
+    fn new(
+        cursor: C,
+        start: Bound<Data>,
+        end: Bound<Data>,
+        key_cmp: KeyCmp,
+        duplicate_values: bool,
+    ) -> Self {
+        let _phantom = std::marker::PhantomData;
+        RangeIterRev {
+            cursor,
+            positioned: false,
+            failed: false,
+            start,
+            end,
+            key_cmp,
+            duplicate_values,
+            _phantom,
+        }
+    }
+
+    /// Move the cursor to the last entry within the end bound
+    fn seek_end(&self) -> lmdb::Result<(Option<&'tx [u8]>, &'tx [u8])> {
+        let key = match &self.end {
+            // LMDB does not support empty keys, so nothing can lie within an empty end bound
+            Bound::Included(key) | Bound::Excluded(key) if key.is_empty() => {
+                return Err(lmdb::Error::NotFound)
+            }
+            Bound::Included(key) | Bound::Excluded(key) => key,
+            Bound::Unbounded => return self.cursor.get(None, None, cursor_op::LAST),
+        };
+        // Find the first entry not less than the end bound and step back if it is past the end
+        match self.cursor.get(Some(key), None, cursor_op::SET_RANGE) {
+            Ok((Some(k), _)) if self.key_cmp.is_past_end(&self.end, k) => {
+                self.cursor.get(None, None, cursor_op::PREV)
+            }
+            // The cursor is at the first of the values stored under the included end key, move
+            // to the last one. The key is not returned by this operation.
+            Ok((Some(k), _)) if self.duplicate_values && self.key_cmp.cmp(k, key).is_eq() => {
+                let (_, v) = self.cursor.get(None, None, cursor_op::LAST_DUP)?;
+                Ok((Some(k), v))
+            }
+            Err(lmdb::Error::NotFound) => self.cursor.get(None, None, cursor_op::LAST),
+            res => res,
+        }
+    }
+}
+
+impl<'tx, C: Cursor<'tx>> Iterator for RangeIterRev<'tx, C> {
+    type Item = storage_core::Result<backend::Entry<'tx>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        utils::ensure!(!self.failed);
+        let res = if self.positioned {
+            self.cursor.get(None, None, cursor_op::PREV)
+        } else {
+            self.positioned = true;
+            self.seek_end()
+        };
+        let (k, v) = match res {
+            Ok(entry) => entry,
+            Err(lmdb::Error::NotFound) => return None,
+            Err(err) => {
+                self.failed = true;
+                return Some(error::process_with_err(err));
+            }
+        };
+        let k = k.expect("cursor operation to return the key");
+        utils::ensure!(!self.key_cmp.is_before_start(&self.start, k));
+        Some(Ok((k.into(), v.into())))
+    }
+}
+
+/// LMDB cursor over the entries of a database
+pub struct DbCursor<'tx, C> {
+    /// Underlying cursor
+    cursor: C,
+
+    /// The entry the cursor is positioned at
+    current: Option<(Data, Data)>,
+
+    _phantom: std::marker::PhantomData<&'tx ()>,
+}
+
+impl<'tx, C: Cursor<'tx>> DbCursor<'tx, C> {
+    fn new(cursor: C) -> Self {
+        let _phantom = std::marker::PhantomData;
+        let current = None;
+        DbCursor {
+            cursor,
+            current,
+            _phantom,
+        }
+    }
+
+    /// Perform a cursor operation, recording the entry the cursor ends up at
+    fn op(
+        &mut self,
+        key: Option<&[u8]>,
+        op: std::ffi::c_uint,
+    ) -> storage_core::Result<Option<(Data, Data)>> {
+        let entry = match self.cursor.get(key, None, op) {
+            Ok((k, v)) => {
+                let k = k.expect("cursor operation to return the key");
+                Ok(Some((k.to_vec(), v.to_vec())))
+            }
+            Err(err) => error::process_with_none(err),
+        };
+        // A failed operation leaves the cursor unpositioned
+        self.current = entry.clone().unwrap_or(None);
+        entry
+    }
+}
+
+impl<'tx, C: Cursor<'tx>> backend::Cursor for DbCursor<'tx, C> {
+    fn seek(&mut self, key: &[u8]) -> storage_core::Result<Option<(Data, Data)>> {
+        // LMDB does not support empty keys, all keys are greater than the empty one
+        if key.is_empty() {
+            return self.first();
+        }
+        self.op(Some(key), cursor_op::SET_RANGE)
+    }
+
+    fn first(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        self.op(None, cursor_op::FIRST)
+    }
+
+    fn last(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        self.op(None, cursor_op::LAST)
+    }
+
+    fn next(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        // The position of an LMDB cursor that moved past either end is not well defined, so
+        // the unpositioned case is handled explicitly
+        match self.current {
+            Some(_) => self.op(None, cursor_op::NEXT),
+            None => self.first(),
+        }
+    }
+
+    fn prev(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        match self.current {
+            Some(_) => self.op(None, cursor_op::PREV),
+            None => self.last(),
+        }
+    }
+
+    fn current(&self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.current.clone())
+    }
+}
+
+/// Stack of nested transactions backing the active savepoints, innermost last
+struct NestedTxs<Tx>(Vec<Tx>);
+
+impl<Tx> Drop for NestedTxs<Tx> {
+    fn drop(&mut self) {
+        // Child transactions have to be aborted before their parents
+        while let Some(tx) = self.0.pop() {
+            drop(tx);
+        }
     }
 }
 
 pub struct DbTx<'m, Tx> {
+    // Declared before the main transaction so the nested ones are dropped first
+    nested: NestedTxs<Tx>,
     tx: Tx,
     backend: &'m LmdbImpl,
 }
 
+impl<Tx> DbTx<'_, Tx> {
+    /// The innermost active transaction. The enclosing transactions must not be used while
+    /// a nested one is active.
+    fn tx(&self) -> &Tx {
+        self.nested.0.last().unwrap_or(&self.tx)
+    }
+
+    fn tx_mut(&mut self) -> &mut Tx {
+        self.nested.0.last_mut().unwrap_or(&mut self.tx)
+    }
+
+    /// Check given prefix can be used to select keys of given map. Keys sharing a prefix are
+    /// only adjacent in maps with the lexicographic key order, in others only the empty prefix,
+    /// selecting the whole map, is supported.
+    fn check_prefix(&self, map_id: DbMapId, prefix: &[u8]) -> storage_core::Result<()> {
+        let key_order = self.backend.key_orders[map_id];
+        utils::ensure!(
+            prefix.is_empty() || key_order == storage_core::KeyOrder::Lexicographic,
+            storage_core::error::Fatal::UnsupportedOperation(format!(
+                "Prefix selection in map {} with {key_order:?} key order",
+                self.backend.map_names[map_id]
+            ))
+        );
+        Ok(())
+    }
+
+    /// Check the range bounds are valid keys to compare with the keys of given map
+    fn check_bounds(
+        &self,
+        map_id: DbMapId,
+        start: &Bound<Data>,
+        end: &Bound<Data>,
+    ) -> storage_core::Result<()> {
+        if self.backend.key_orders[map_id] == storage_core::KeyOrder::Integer {
+            for bound in [start, end] {
+                if let Bound::Included(key) | Bound::Excluded(key) = bound {
+                    utils::ensure!(
+                        key.len() == 4 || key.len() == 8,
+                        storage_core::error::Fatal::UnsupportedOperation(format!(
+                            "Range bound of {} bytes in map {} with integer keys",
+                            key.len(),
+                            self.backend.map_names[map_id]
+                        ))
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 type DbTxRo<'a> = DbTx<'a, lmdb::RoTransaction<'a>>;
 type DbTxRw<'a> = DbTx<'a, lmdb::RwTransaction<'a>>;
 
 impl<Tx: lmdb::Transaction> backend::ReadOps for DbTx<'_, Tx> {
     type PrefixIter<'i> = PrefixIter<'i, lmdb::RoCursor<'i>> where Self: 'i;
+    type PrefixKeyIter<'i> = PrefixKeyIter<'i, lmdb::RoCursor<'i>> where Self: 'i;
+    type RangeIter<'i> = RangeIter<'i, lmdb::RoCursor<'i>> where Self: 'i;
+    type RangeIterRev<'i> = RangeIterRev<'i, lmdb::RoCursor<'i>> where Self: 'i;
+    type Cursor<'i> = DbCursor<'i, lmdb::RoCursor<'i>> where Self: 'i;
 
     fn get(&self, map_id: DbMapId, key: &[u8]) -> storage_core::Result<Option<Cow<[u8]>>> {
-        self.tx
+        self.tx()
             .get(self.backend.dbs[map_id], &key)
             .map_or_else(error::process_with_none, |x| Ok(Some(x.into())))
     }
@@ -83,8 +475,9 @@ impl<Tx: lmdb::Transaction> backend::ReadOps for DbTx<'_, Tx> {
         map_id: DbMapId,
         prefix: Data,
     ) -> storage_core::Result<Self::PrefixIter<'_>> {
+        self.check_prefix(map_id, &prefix)?;
         let cursor = self
-            .tx
+            .tx()
             .open_ro_cursor(self.backend.dbs[map_id])
             .or_else(error::process_with_err)?;
         let iter = if prefix.is_empty() {
@@ -94,31 +487,261 @@ impl<Tx: lmdb::Transaction> backend::ReadOps for DbTx<'_, Tx> {
         };
         Ok(PrefixIter::new(iter, prefix))
     }
+
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> storage_core::Result<Self::PrefixKeyIter<'_>> {
+        self.prefix_iter(map_id, prefix).map(PrefixKeyIter)
+    }
+
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIter<'_>> {
+        self.check_bounds(map_id, &start, &end)?;
+        let cursor = self
+            .tx()
+            .open_ro_cursor(self.backend.dbs[map_id])
+            .or_else(error::process_with_err)?;
+        // Seek to the first key not less than the start bound. Keys equal to an excluded start
+        // bound are skipped by the iterator itself.
+        let iter = match &start {
+            Bound::Included(key) | Bound::Excluded(key) if !key.is_empty() => {
+                cursor.into_iter_from(key.as_slice())
+            }
+            _ => cursor.into_iter_start(),
+        };
+        let key_cmp = KeyCmp::new(self.tx(), self.backend.dbs[map_id]);
+        Ok(RangeIter::new(iter, start, end, key_cmp))
+    }
+
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIterRev<'_>> {
+        self.check_bounds(map_id, &start, &end)?;
+        let cursor = self
+            .tx()
+            .open_ro_cursor(self.backend.dbs[map_id])
+            .or_else(error::process_with_err)?;
+        let key_cmp = KeyCmp::new(self.tx(), self.backend.dbs[map_id]);
+        let duplicate_values = self.backend.duplicate_values[map_id];
+        Ok(RangeIterRev::new(
+            cursor,
+            start,
+            end,
+            key_cmp,
+            duplicate_values,
+        ))
+    }
+
+    fn prefix_iter_rev(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> storage_core::Result<Self::RangeIterRev<'_>> {
+        self.check_prefix(map_id, &prefix)?;
+        let (start, end) = if prefix.is_empty() {
+            (Bound::Unbounded, Bound::Unbounded)
+        } else {
+            util::prefix_range(prefix)
+        };
+        backend::ReadOps::range_iter_rev(self, map_id, start, end)
+    }
+
+    fn cursor(&self, map_id: DbMapId) -> storage_core::Result<Self::Cursor<'_>> {
+        let cursor = self
+            .tx()
+            .open_ro_cursor(self.backend.dbs[map_id])
+            .or_else(error::process_with_err)?;
+        Ok(DbCursor::new(cursor))
+    }
 }
 
 impl backend::WriteOps for DbTx<'_, lmdb::RwTransaction<'_>> {
     fn put(&mut self, map_id: DbMapId, key: Data, val: Data) -> storage_core::Result<()> {
         let db = self.backend.dbs[map_id];
-        self.tx
+        self.tx_mut()
             .put(db, &key, &val, lmdb::WriteFlags::empty())
             .map_err(|err| self.backend.schedule_map_resize_if_map_full(err))
             .or_else(error::process_with_unit)
     }
 
     fn del(&mut self, map_id: DbMapId, key: &[u8]) -> storage_core::Result<()> {
-        self.tx
-            .del(self.backend.dbs[map_id], &key, None)
+        let db = self.backend.dbs[map_id];
+        self.tx_mut()
+            .del(db, &key, None)
             .map_err(|err| self.backend.schedule_map_resize_if_map_full(err))
             .or_else(error::process_with_unit)
     }
+
+    fn del_range(
+        &mut self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<()> {
+        self.check_bounds(map_id, &start, &end)?;
+        let backend = self.backend;
+        let key_cmp = KeyCmp::new(self.tx(), backend.dbs[map_id]);
+        let mut cursor = self
+            .tx_mut()
+            .open_rw_cursor(backend.dbs[map_id])
+            .or_else(error::process_with_err)?;
+
+        let mut entry = match &start {
+            Bound::Included(key) | Bound::Excluded(key) if !key.is_empty() => {
+                cursor.get(Some(key), None, cursor_op::SET_RANGE)
+            }
+            _ => cursor.get(None, None, cursor_op::FIRST),
+        };
+
+        loop {
+            let key = match entry {
+                Ok((key, _)) => key.expect("cursor operation to return the key"),
+                Err(err) => return error::process_with_unit(err),
+            };
+            if key_cmp.is_past_end(&end, key) {
+                return Ok(());
+            }
+            if !key_cmp.is_before_start(&start, key) {
+                cursor
+                    .del(lmdb::WriteFlags::empty())
+                    .map_err(|err| backend.schedule_map_resize_if_map_full(err))
+                    .or_else(error::process_with_err)?;
+            }
+            // After a deletion, the cursor moves on to the entry following the deleted one
+            entry = cursor.get(None, None, cursor_op::NEXT);
+        }
+    }
+
+    fn del_prefix(&mut self, map_id: DbMapId, prefix: Data) -> storage_core::Result<()> {
+        self.check_prefix(map_id, &prefix)?;
+        if prefix.is_empty() {
+            return backend::WriteOps::clear(self, map_id);
+        }
+        let (start, end) = util::prefix_range(prefix);
+        backend::WriteOps::del_range(self, map_id, start, end)
+    }
+
+    fn clear(&mut self, map_id: DbMapId) -> storage_core::Result<()> {
+        let db = self.backend.dbs[map_id];
+        self.tx_mut().clear_db(db).or_else(error::process_with_unit)
+    }
+
+    fn put_if_absent(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        val: Data,
+    ) -> storage_core::Result<bool> {
+        let db = self.backend.dbs[map_id];
+        match self.tx_mut().put(db, &key, &val, lmdb::WriteFlags::NO_OVERWRITE) {
+            Ok(()) => Ok(true),
+            Err(lmdb::Error::KeyExist) => Ok(false),
+            Err(err) => error::process_with_err(self.backend.schedule_map_resize_if_map_full(err)),
+        }
+    }
+
+    fn compare_and_swap(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> storage_core::Result<bool> {
+        // LMDB allows only one write transaction at a time, so the check and the subsequent
+        // update cannot be interleaved with other writes
+        if self.backend.duplicate_values[map_id] {
+            return self.compare_and_swap_dup(map_id, key, expected, new);
+        }
+        let matches = backend::ReadOps::get(self, map_id, &key)?.as_deref() == expected;
+        if matches {
+            match new {
+                Some(new) => backend::WriteOps::put(self, map_id, key, new)?,
+                None => backend::WriteOps::del(self, map_id, &key)?,
+            }
+        }
+        Ok(matches)
+    }
+}
+
+impl DbTx<'_, lmdb::RwTransaction<'_>> {
+    /// Conditional write in a map with duplicate values, where only the expected value is
+    /// replaced rather than all the values of the key
+    fn compare_and_swap_dup(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> storage_core::Result<bool> {
+        let db = self.backend.dbs[map_id];
+        let matches = match expected {
+            // Deleting a particular value fails if the key does not hold it
+            Some(expected) => match self.tx_mut().del(db, &key, Some(expected)) {
+                Ok(()) => true,
+                Err(lmdb::Error::NotFound) => false,
+                Err(err) => {
+                    return error::process_with_err(
+                        self.backend.schedule_map_resize_if_map_full(err),
+                    )
+                }
+            },
+            None => backend::ReadOps::get(self, map_id, &key)?.is_none(),
+        };
+        if let (true, Some(new)) = (matches, new) {
+            backend::WriteOps::put(self, map_id, key, new)?;
+        }
+        Ok(matches)
+    }
 }
 
 impl backend::TxRo for DbTxRo<'_> {}
 
-impl backend::TxRw for DbTxRw<'_> {
-    fn commit(self) -> storage_core::Result<()> {
-        lmdb::Transaction::commit(self.tx)
-            .map_err(|e| self.backend.resize_if_map_full(e))
+impl<'a> backend::TxRw for DbTxRw<'a> {
+    fn commit(mut self) -> storage_core::Result<()> {
+        // Fold the nested transactions of any active savepoints into the main one first
+        while !self.nested.0.is_empty() {
+            self.release()?;
+        }
+        lmdb::Transaction::commit(self.tx).or_else(|e| {
+            self.backend.resize_if_map_full(e)?;
+            error::process_with_unit(e)
+        })
+    }
+
+    fn savepoint(&mut self) -> storage_core::Result<()> {
+        let nested = self.tx_mut().begin_nested_txn().or_else(error::process_with_err)?;
+        // SAFETY: The nested transaction borrows its parent mutably, which cannot be expressed
+        // by storing both in the same struct. Extending the lifetime is sound because:
+        // * All operations go through the innermost transaction, the parent is not touched
+        //   until the nested transaction is committed or aborted.
+        // * Nested transactions are always dropped before their parents, see `NestedTxs`.
+        // * The environment outlives the main transaction and hence all the nested ones.
+        let nested = unsafe {
+            std::mem::transmute::<lmdb::RwTransaction<'_>, lmdb::RwTransaction<'a>>(nested)
+        };
+        self.nested.0.push(nested);
+        Ok(())
+    }
+
+    fn rollback_to(&mut self) -> storage_core::Result<()> {
+        let nested = self.nested.0.pop().ok_or(storage_core::error::Fatal::NoSavepoint)?;
+        lmdb::Transaction::abort(nested);
+        Ok(())
+    }
+
+    fn release(&mut self) -> storage_core::Result<()> {
+        let nested = self.nested.0.pop().ok_or(storage_core::error::Fatal::NoSavepoint)?;
+        lmdb::Transaction::commit(nested)
+            .map_err(|e| self.backend.schedule_map_resize_if_map_full(e))
             .or_else(error::process_with_unit)
     }
 }
@@ -131,23 +754,137 @@ pub struct LmdbImpl {
     /// List of open databases
     dbs: ConstValue<DbMapsData<lmdb::Database>>,
 
+    /// Names of the open databases
+    map_names: ConstValue<DbMapsData<String>>,
+
+    /// Key order of each of the open databases
+    key_orders: ConstValue<DbMapsData<storage_core::KeyOrder>>,
+
+    /// Whether each of the open databases allows multiple values per key
+    duplicate_values: ConstValue<DbMapsData<bool>>,
+
     /// Schedule a database resize of the database map
     map_resize_scheduled: Arc<AtomicBool>,
+
+    /// Automatic checks for stale readers
+    reader_check: Arc<readers::AutoCheck>,
+
+    /// Share of the map that has to be in use for it to be resized, in per-mille
+    resize_trigger_permille: usize,
+
+    /// Location of the data file
+    data_file: Arc<PathBuf>,
+
+    /// Whether the data is stored directly in a file rather than in a directory
+    no_sub_dir: bool,
 }
 
+/// Share of the map that has to be in use for it to be resized according to given settings, in
+/// per-mille. Settings outside of the range (0, 1] stand for the whole map.
+// The setting is a float in the underlying library, this is the one place it is converted
+#[allow(clippy::float_arithmetic)]
+fn resize_trigger_permille(resize_settings: &DatabaseResizeSettings) -> usize {
+    match resize_settings.resize_trigger_percentage {
+        trigger if trigger > 0.0 && trigger <= 1.0 => ((trigger * 1000.0).round() as usize).max(1),
+        _ => 1000,
+    }
+}
+
+/// Number of times resizing the database map is attempted before the failure is reported
+const MAX_RESIZE_ATTEMPTS: u32 = 5;
+
+/// Delay before the second attempt to resize the database map, doubled for each further attempt.
+/// This gives readers still using the old map some time to finish, 15ms in total.
+const RESIZE_RETRY_DELAY: Duration = Duration::from_millis(1);
+
 impl LmdbImpl {
     /// Start a transaction using the low-level method provided
     fn start_transaction<'a, Tx: 'a>(
         &'a self,
         start_tx: impl FnOnce(&'a lmdb::Environment) -> Result<Tx, lmdb::Error>,
     ) -> storage_core::Result<DbTx<'a, Tx>> {
+        self.reader_check.run_if_due(&self.env).or_else(error::process_with_err)?;
+
         // Make sure map token is acquired before starting the transaction below
         Ok(DbTx {
+            nested: NestedTxs(Vec::new()),
             tx: start_tx(&self.env).or_else(error::process_with_err)?,
             backend: self,
         })
     }
 
+    /// Statistics of the environment and each of the database maps.
+    ///
+    /// The map statistics are taken in a read-only transaction so, unless the environment has
+    /// been opened with [Lmdb::with_no_tls], the calling thread must not hold another one.
+    pub fn stats(&self) -> storage_core::Result<stats::EnvStats> {
+        let info = self.env.info().or_else(error::process_with_err)?;
+        let page_size = self.env.stat().or_else(error::process_with_err)?.page_size();
+
+        let tx = self.env.begin_ro_txn().or_else(error::process_with_err)?;
+        let maps = self
+            .dbs
+            .db_map_count()
+            .indices()
+            .map(|map_id| {
+                let stat = lmdb::Transaction::stat(&tx, self.dbs[map_id])
+                    .or_else(error::process_with_err)?;
+                Ok((
+                    self.map_names[map_id].clone(),
+                    stats::MapStats::from_lmdb(&stat),
+                ))
+            })
+            .collect::<storage_core::Result<_>>()?;
+
+        Ok(stats::EnvStats {
+            map_size: info.map_size(),
+            page_size,
+            last_page_number: info.last_pgno(),
+            last_transaction_id: info.last_txnid(),
+            max_readers: info.max_readers(),
+            num_readers: info.num_readers(),
+            maps,
+        })
+    }
+
+    /// Clear the reader slots left behind by processes that are no longer running, returning the
+    /// number of slots cleared
+    pub fn check_readers(&self) -> storage_core::Result<usize> {
+        self.reader_check.run(&self.env).or_else(error::process_with_err)
+    }
+
+    /// Total number of stale reader slots cleared since the database has been opened, including
+    /// the automatic checks
+    pub fn stale_readers_cleared(&self) -> usize {
+        self.reader_check.cleared()
+    }
+
+    /// Copy the database while it remains in use, optionally compacting it by omitting the free
+    /// pages.
+    ///
+    /// The target is a directory which must not contain a database yet, or the path to the new
+    /// data file if the database has been opened with [Lmdb::with_no_sub_dir]. The copy is taken
+    /// in a read-only transaction so, unless the environment has been opened with
+    /// [Lmdb::with_no_tls], the calling thread must not hold another one.
+    pub fn backup(
+        &self,
+        target: impl AsRef<Path>,
+        compact: bool,
+    ) -> storage_core::Result<backup::CopyStats> {
+        backup::copy(
+            &self.env,
+            &self.data_file,
+            target.as_ref(),
+            self.no_sub_dir,
+            compact,
+        )
+    }
+
+    /// Human-readable dump of the reader lock table, for debugging
+    pub fn reader_list(&self) -> storage_core::Result<String> {
+        readers::list(&self.env).or_else(error::process_with_err)
+    }
+
     fn schedule_map_resize(&self) {
         self.map_resize_scheduled.store(true, Ordering::Release);
     }
@@ -156,28 +893,83 @@ impl LmdbImpl {
         self.map_resize_scheduled.store(false, Ordering::Release);
     }
 
-    fn resize_if_resize_scheduled(&self) {
+    /// Grow the database map. Attempts failing with a transient error, see
+    /// [error::is_transient_resize_error], are repeated up to [MAX_RESIZE_ATTEMPTS] times with an
+    /// increasing delay in between, see [RESIZE_RETRY_DELAY]. If it still fails, the resize stays
+    /// scheduled and the last error is returned.
+    ///
+    /// The map grows by `increase` bytes if given, otherwise by the default ratio of its current
+    /// size, limited by the resize settings.
+    fn resize(&self, increase: Option<usize>) -> storage_core::Result<()> {
+        let mut attempts = 1;
+        let mut delay = RESIZE_RETRY_DELAY;
+        loop {
+            let err = match self.env.do_resize(increase) {
+                Ok(()) => {
+                    self.unschedule_map_resize();
+                    return Ok(());
+                }
+                Err(err) => err,
+            };
+
+            if attempts >= MAX_RESIZE_ATTEMPTS || !error::is_transient_resize_error(&err) {
+                self.schedule_map_resize();
+                return error::process_resize_error(err);
+            }
+
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempts += 1;
+        }
+    }
+
+    fn resize_if_resize_scheduled(&self) -> storage_core::Result<()> {
         // simulate an atomic test_and_set(), where we check if a resize is scheduled, and we also set it to false
         if self
             .map_resize_scheduled
             .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
             .unwrap_or(false)
         {
-            self.env.do_resize(None).expect("Failed to resize after a trigger to resize");
+            self.resize(None)?;
+        }
+        Ok(())
+    }
+
+    /// Grow the map ahead of a write transaction expected to add `size` bytes if the free space
+    /// is insufficient, so that the transaction does not have to be redone after filling the map
+    fn resize_for_transaction(&self, size: usize) -> storage_core::Result<()> {
+        loop {
+            let info = self.env.info().or_else(error::process_with_err)?;
+            let page_size = self.env.stat().or_else(error::process_with_err)?.page_size();
+            let map_size = info.map_size();
+            let required = ((info.last_pgno() + 1) * page_size as usize).saturating_add(size);
+
+            // Same threshold as when resizing in response to the map being filled
+            let target = required.checked_mul(1000).map_or(usize::MAX, |scaled| {
+                scaled.div_ceil(self.resize_trigger_permille)
+            });
+            if target <= map_size {
+                return Ok(());
+            }
+
+            self.resize(Some(target - map_size))?;
+
+            // Give up if the resize settings do not allow the map to grow any further
+            let new_map_size = self.env.info().or_else(error::process_with_err)?.map_size();
+            if new_map_size <= map_size {
+                return Ok(());
+            }
         }
     }
 
     /// If the lmdb map is full, perform a resize. This results in fixing
     /// a recoverable error of MDB_MAP_FULL to work out-of-the-box by just
     /// retrying one or more times
-    fn resize_if_map_full(&self, err: lmdb::Error) -> lmdb::Error {
+    fn resize_if_map_full(&self, err: lmdb::Error) -> storage_core::Result<()> {
         if err == lmdb::Error::MapFull {
-            self.env
-                .do_resize(None)
-                .expect("Failed to resize after a write/commit failed with MDB_MAP_FULL");
-            self.unschedule_map_resize();
+            self.resize(None)?;
         }
-        err
+        Ok(())
     }
 
     fn schedule_map_resize_if_map_full(&self, err: lmdb::Error) -> lmdb::Error {
@@ -193,7 +985,14 @@ impl utils::shallow_clone::ShallowClone for LmdbImpl {
         Self {
             env: self.env.shallow_clone(),
             dbs: self.dbs.shallow_clone(),
+            map_names: self.map_names.shallow_clone(),
+            key_orders: self.key_orders.shallow_clone(),
+            duplicate_values: self.duplicate_values.shallow_clone(),
             map_resize_scheduled: self.map_resize_scheduled.shallow_clone(),
+            reader_check: self.reader_check.shallow_clone(),
+            resize_trigger_permille: self.resize_trigger_permille,
+            data_file: self.data_file.shallow_clone(),
+            no_sub_dir: self.no_sub_dir,
         }
     }
 }
@@ -207,14 +1006,23 @@ impl backend::BackendImpl for LmdbImpl {
     }
 
     fn transaction_rw(&self, size: Option<usize>) -> storage_core::Result<Self::TxRw<'_>> {
-        self.resize_if_resize_scheduled();
+        self.resize_if_resize_scheduled()?;
+        if let Some(size) = size {
+            self.resize_for_transaction(size)?;
+        }
         self.start_transaction(|env| lmdb::Environment::begin_rw_txn(env, size))
     }
 }
 
+/// Permissions of the database files unless specified otherwise
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
 pub struct Lmdb {
     path: PathBuf,
     flags: lmdb::EnvironmentFlags,
+    max_readers: Option<u32>,
+    file_mode: u32,
+    reader_check_interval: Option<Duration>,
     initial_map_size: InitialMapSize,
     resize_settings: DatabaseResizeSettings,
     resize_callback: MapResizeCallback,
@@ -231,6 +1039,9 @@ impl Lmdb {
         Self {
             path,
             flags: lmdb::EnvironmentFlags::default(),
+            max_readers: None,
+            file_mode: DEFAULT_FILE_MODE,
+            reader_check_interval: None,
             initial_map_size,
             resize_settings,
             resize_callback,
@@ -241,30 +1052,224 @@ impl Lmdb {
     ///
     /// This disables some protections in exchange for better performance.
     /// See [lmdb::EnvironmentFlags::WRITE_MAP] for more detail.
+    ///
+    /// Note savepoints are not available with a writable memory map as they are implemented
+    /// using nested transactions which LMDB does not support in this mode.
     pub fn with_write_map(mut self) -> Self {
         self.flags |= lmdb::EnvironmentFlags::WRITE_MAP;
         self
     }
 
+    /// Do not flush system buffers to disk when committing a transaction.
+    ///
+    /// A system crash may undo the last transactions or, with a writable memory map, corrupt the
+    /// database. See [lmdb::EnvironmentFlags::NO_SYNC] for more detail.
+    pub fn with_no_sync(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_SYNC;
+        self
+    }
+
+    /// Flush system buffers to disk only once per transaction, omitting the metadata flush.
+    ///
+    /// A system crash may undo the last committed transaction.
+    /// See [lmdb::EnvironmentFlags::NO_META_SYNC] for more detail.
+    pub fn with_no_meta_sync(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_META_SYNC;
+        self
+    }
+
+    /// Flush the writable memory map to disk asynchronously.
+    ///
+    /// Requires [Self::with_write_map]. See [lmdb::EnvironmentFlags::MAP_ASYNC] for more detail.
+    pub fn with_map_async(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::MAP_ASYNC;
+        self
+    }
+
+    /// Turn off the OS readahead on the memory map.
+    ///
+    /// This may improve random read performance when the database is larger than RAM.
+    /// See [lmdb::EnvironmentFlags::NO_READAHEAD] for more detail.
+    pub fn with_no_readahead(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_READAHEAD;
+        self
+    }
+
+    /// Tie reader slots to transactions rather than threads.
+    ///
+    /// This allows a thread to have more than one read-only transaction at a time and read-only
+    /// transactions to be moved between threads.
+    /// See [lmdb::EnvironmentFlags::NO_TLS] for more detail.
+    pub fn with_no_tls(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_TLS;
+        self
+    }
+
+    /// Do not use locking for concurrent access.
+    ///
+    /// The caller has to make sure there is no more than one writer at a time and that no
+    /// readers use pages a writer may be reusing, e.g. by not sharing the database with other
+    /// processes and running all transactions one at a time. Hence this cannot be combined with
+    /// more than one reader, [Self::with_no_tls] or [Self::with_reader_check_interval].
+    /// See [lmdb::EnvironmentFlags::NO_LOCK] for more detail.
+    pub fn with_no_lock(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_LOCK;
+        self
+    }
+
+    /// Maximum number of read-only transactions that can be active at the same time, across all
+    /// processes using the database. Defaults to 126.
+    pub fn with_max_readers(mut self, max_readers: u32) -> Self {
+        self.max_readers = Some(max_readers);
+        self
+    }
+
+    /// Unix permissions of the database files when they are created, `0o644` by default.
+    ///
+    /// The owner has to be able to both read and write the files.
+    pub fn with_file_mode(mut self, file_mode: u32) -> Self {
+        self.file_mode = file_mode;
+        self
+    }
+
+    /// Store the data in a file at the database path rather than in a directory.
+    ///
+    /// The lock file is then placed next to it with `-lock` appended to the name.
+    /// See [lmdb::EnvironmentFlags::NO_SUB_DIR] for more detail.
+    pub fn with_no_sub_dir(mut self) -> Self {
+        self.flags |= lmdb::EnvironmentFlags::NO_SUB_DIR;
+        self
+    }
+
+    /// Periodically clear the reader slots left behind by processes that are no longer running.
+    ///
+    /// Stale readers are always cleared when the database is opened. With this setting, the
+    /// check is repeated when a transaction is started after given interval has elapsed since
+    /// the last one.
+    pub fn with_reader_check_interval(mut self, interval: Duration) -> Self {
+        self.reader_check_interval = Some(interval);
+        self
+    }
+
+    /// Compact the database, shrinking the data file to the space actually in use.
+    ///
+    /// The database is copied with the free pages omitted and the copy then atomically replaces
+    /// the original data file. It must not be opened by anyone else in the meantime.
+    pub fn compact_in_place(mut self) -> storage_core::Result<backup::CopyStats> {
+        let no_sub_dir = self.flags.contains(lmdb::EnvironmentFlags::NO_SUB_DIR);
+        backup::remove_compact_target(&self.path, no_sub_dir)?;
+
+        let environment = self.open_environment(0)?;
+        let stats = backup::copy(
+            &environment,
+            &backup::data_file(&self.path, no_sub_dir),
+            &backup::compact_target(&self.path, no_sub_dir),
+            no_sub_dir,
+            true,
+        );
+        drop(environment);
+
+        match stats {
+            Ok(stats) => {
+                backup::swap_in_compacted(&self.path, no_sub_dir)?;
+                Ok(stats)
+            }
+            Err(err) => {
+                backup::remove_compact_target(&self.path, no_sub_dir)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Check the settings are consistent with each other
+    fn validate(&self) -> storage_core::Result<()> {
+        let invalid =
+            |msg: &str| Err(storage_core::error::Fatal::InvalidOptions(msg.into()).into());
+
+        if self.flags.contains(lmdb::EnvironmentFlags::MAP_ASYNC)
+            && !self.flags.contains(lmdb::EnvironmentFlags::WRITE_MAP)
+        {
+            return invalid("asynchronous map flushes require a writable memory map");
+        }
+
+        if self.max_readers == Some(0) {
+            return invalid("the maximum number of readers must be positive");
+        }
+
+        // Without locking, there is no reader table and transactions have to run one at a time
+        if self.flags.contains(lmdb::EnvironmentFlags::NO_LOCK) {
+            if self.max_readers.is_some_and(|max_readers| max_readers > 1) {
+                return invalid("more than one reader requires locking");
+            }
+            if self.flags.contains(lmdb::EnvironmentFlags::NO_TLS) {
+                return invalid("reader slots tied to transactions require locking");
+            }
+            if self.reader_check_interval.is_some() {
+                return invalid("stale reader checks require locking");
+            }
+        }
+
+        if self.file_mode & !0o777 != 0 {
+            return invalid("file mode may only contain permission bits");
+        }
+
+        if self.file_mode & 0o600 != 0o600 {
+            return invalid("file mode has to allow the owner to read and write");
+        }
+
+        Ok(())
+    }
+
     fn open_db(env: &lmdb::Environment, desc: &DbMapDesc) -> storage_core::Result<lmdb::Database> {
         let name = Some(desc.name());
-        let flags = lmdb::DatabaseFlags::default();
-        env.create_db(name, flags).or_else(error::process_with_err)
+        let mut flags = match desc.key_order() {
+            storage_core::KeyOrder::Lexicographic => lmdb::DatabaseFlags::default(),
+            storage_core::KeyOrder::Reverse => lmdb::DatabaseFlags::REVERSE_KEY,
+            storage_core::KeyOrder::Integer => lmdb::DatabaseFlags::INTEGER_KEY,
+        };
+        if desc.duplicate_values() {
+            flags |= lmdb::DatabaseFlags::DUP_SORT;
+            if desc.value_size_hint().len() == 1 {
+                flags |= lmdb::DatabaseFlags::DUP_FIXED;
+            }
+        }
+        let db = env.create_db(name, flags).or_else(error::process_with_err)?;
+
+        // An existing database keeps the flags it has been created with
+        let db_flags = env.get_db_flags(db).or_else(error::process_with_err)?;
+        utils::ensure!(
+            db_flags == flags,
+            storage_core::error::Fatal::SchemaMismatch(format!(
+                "Map {} has been created with a different layout",
+                desc.name()
+            ))
+        );
+        Ok(db)
     }
-}
 
-impl backend::Backend for Lmdb {
-    type Impl = LmdbImpl;
+    /// Set up the LMDB environment with room for given number of database maps
+    fn open_environment(&mut self, max_dbs: u32) -> storage_core::Result<lmdb::Environment> {
+        self.validate()?;
 
-    fn open(self, desc: DbDesc) -> storage_core::Result<Self::Impl> {
-        // Attempt to create the storage directory
-        std::fs::create_dir_all(&self.path).map_err(error::process_io_error)?;
+        // Attempt to create the storage directory, or the one containing the data file
+        let dir = if self.flags.contains(lmdb::EnvironmentFlags::NO_SUB_DIR) {
+            self.path.parent()
+        } else {
+            Some(self.path.as_path())
+        };
+        if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(error::process_io_error)?;
+        }
 
         let initial_map_size = self
             .initial_map_size
             .into_memsize()
             .map(|v| v.as_bytes().try_into().expect("MemSize to usize conversion failed"));
 
+        // The type of file mode is platform dependent
+        #[allow(clippy::useless_conversion)]
+        let file_mode = self.file_mode.try_into().expect("File mode validated to fit");
+
         // Set up LMDB environment
         let environment = lmdb::Environment::new();
 
@@ -272,22 +1277,101 @@ impl backend::Backend for Lmdb {
             environment.set_map_size(sz)
         } else {
             environment
+        };
+
+        let environment = if let Some(max_readers) = self.max_readers {
+            environment.set_max_readers(max_readers)
+        } else {
+            environment
         }
-        .set_resize_settings(self.resize_settings)
-        .set_resize_callback(self.resize_callback.take())
-        .set_max_dbs(desc.db_map_count().as_usize() as u32)
+        .set_resize_settings(self.resize_settings.clone())
+        .set_resize_callback(std::mem::take(&mut self.resize_callback).into_lmdb())
+        .set_max_dbs(max_dbs)
         .set_flags(self.flags)
-        .open(&self.path)
-        .or_else(error::process_with_err)?;
+        .open_with_permissions(&self.path, file_mode);
+
+        environment.or_else(error::process_with_err)
+    }
+
+    /// Read all entries of the database with given name if it exists, without creating it
+    fn read_existing_db(
+        env: &lmdb::Environment,
+        name: &str,
+    ) -> storage_core::Result<BTreeMap<Data, Data>> {
+        let db = match env.open_db(Some(name)) {
+            Ok(db) => db,
+            Err(lmdb::Error::NotFound) => return Ok(BTreeMap::new()),
+            Err(err) => return error::process_with_err(err),
+        };
+        let tx = env.begin_ro_txn().or_else(error::process_with_err)?;
+        let mut cursor =
+            lmdb::Transaction::open_ro_cursor(&tx, db).or_else(error::process_with_err)?;
+        // Collected into a variable so that the iterator borrowing the cursor is dropped first
+        let entries = cursor
+            .iter_start()
+            .map(|item| match item {
+                Ok((k, v)) => Ok((k.to_vec(), v.to_vec())),
+                Err(err) => error::process_with_err(err),
+            })
+            .collect();
+        entries
+    }
+
+    /// Open the environment, run given check on it and then set up the database maps in it
+    fn open_with_check(
+        mut self,
+        desc: DbDesc,
+        check: impl FnOnce(&lmdb::Environment) -> storage_core::Result<()>,
+    ) -> storage_core::Result<LmdbImpl> {
+        let environment = self.open_environment(desc.db_map_count().as_usize() as u32)?;
+
+        // Clear the slots of readers that did not shut down properly
+        let reader_check = readers::AutoCheck::new(self.reader_check_interval);
+        reader_check.run(&environment).or_else(error::process_with_err)?;
+
+        check(&environment)?;
 
         // Set up all the databases
         let dbs = desc.db_maps().try_transform(|desc| Self::open_db(&environment, desc))?;
         let dbs = dbs.into();
+        let map_names = desc.db_maps().transform(|desc| desc.name().to_owned()).into();
+        let key_orders = desc.db_maps().transform(|desc| desc.key_order()).into();
+        let duplicate_values = desc.db_maps().transform(|desc| desc.duplicate_values()).into();
+
+        let no_sub_dir = self.flags.contains(lmdb::EnvironmentFlags::NO_SUB_DIR);
+        let data_file = backup::data_file(&self.path, no_sub_dir);
 
         Ok(LmdbImpl {
             env: Arc::new(environment),
             dbs,
+            map_names,
+            key_orders,
+            duplicate_values,
             map_resize_scheduled: Arc::new(AtomicBool::new(false)),
+            reader_check: Arc::new(reader_check),
+            resize_trigger_permille: resize_trigger_permille(&self.resize_settings),
+            data_file: Arc::new(data_file),
+            no_sub_dir,
+        })
+    }
+}
+
+impl backend::Backend for Lmdb {
+    type Impl = LmdbImpl;
+
+    fn open(self, desc: DbDesc) -> storage_core::Result<Self::Impl> {
+        self.open_with_check(desc, |_| Ok(()))
+    }
+
+    fn open_checked(
+        self,
+        desc: DbDesc,
+        checked_map: DbMapId,
+        check: impl FnOnce(&BTreeMap<Data, Data>) -> storage_core::Result<()>,
+    ) -> storage_core::Result<Self::Impl> {
+        let name = desc.db_maps()[checked_map].name().to_owned();
+        self.open_with_check(desc, |environment| {
+            check(&Self::read_existing_db(environment, &name)?)
         })
     }
 }
@@ -13,6 +13,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Memory sizes, as used to configure the LMDB memory map
+
+use std::str::FromStr;
+
+/// Decimal units, multiples of 1000 bytes
+const DECIMAL_UNITS: [(&str, u64); 4] = [
+    ("KB", 1_000),
+    ("MB", 1_000_000),
+    ("GB", 1_000_000_000),
+    ("TB", 1_000_000_000_000),
+];
+
+/// Binary units, multiples of 1024 bytes, from the smallest to the largest
+const BINARY_UNITS: [(&str, u64); 4] =
+    [("KiB", 1 << 10), ("MiB", 1 << 20), ("GiB", 1 << 30), ("TiB", 1 << 40)];
+
+/// Maximum number of decimal places shown when displaying a memory size
+const DISPLAY_DECIMALS: u32 = 3;
+
+/// Maximum number of decimal places taken into account when parsing a memory size
+const PARSE_DECIMALS: usize = 18;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone)]
+pub enum MemSizeError {
+    #[error("Invalid number in memory size: '{0}'")]
+    InvalidNumber(String),
+    #[error("Unknown memory size unit: '{0}'")]
+    UnknownUnit(String),
+    #[error("Memory size out of range")]
+    Overflow,
+}
+
 /// Represents LMDB memory map size
 #[derive(Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub struct MemSize(u64);
@@ -25,24 +57,304 @@ impl MemSize {
         Self(bytes)
     }
 
-    /// Specify in the number of kilobytes
+    /// Specify in the number of given units, panicking if the size does not fit
+    const fn from_units(count: u64, unit: u64) -> Self {
+        match count.checked_mul(unit) {
+            Some(bytes) => Self(bytes),
+            None => panic!("Memory size out of range"),
+        }
+    }
+
+    /// Specify in the number of kibibytes, i.e. multiples of 1024 bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size in bytes does not fit into `u64`, as do the constructors below.
+    pub const fn from_kibibytes(kibibytes: u64) -> Self {
+        Self::from_units(kibibytes, 1 << 10)
+    }
+
+    /// Specify in the number of mebibytes
+    pub const fn from_mebibytes(mebibytes: u64) -> Self {
+        Self::from_units(mebibytes, 1 << 20)
+    }
+
+    /// Specify in the number of gibibytes
+    pub const fn from_gibibytes(gibibytes: u64) -> Self {
+        Self::from_units(gibibytes, 1 << 30)
+    }
+
+    /// Specify in the number of tebibytes
+    pub const fn from_tebibytes(tebibytes: u64) -> Self {
+        Self::from_units(tebibytes, 1 << 40)
+    }
+
+    /// Specify in the number of kilobytes, counted as multiples of 1024 bytes like the units of
+    /// the constructors above rather than the `KB` used when parsing
     pub const fn from_kilobytes(kilobytes: u64) -> Self {
-        Self::from_bytes(1024 * kilobytes)
+        Self::from_kibibytes(kilobytes)
     }
 
-    /// Specify in the number of megabytes
+    /// Specify in the number of megabytes, counted as multiples of 1024 kilobytes
     pub const fn from_megabytes(megabytes: u64) -> Self {
-        Self::from_kilobytes(1024 * megabytes)
+        Self::from_mebibytes(megabytes)
+    }
+
+    /// Specify in the number of gigabytes, counted as multiples of 1024 megabytes
+    pub const fn from_gigabytes(gigabytes: u64) -> Self {
+        Self::from_gibibytes(gigabytes)
+    }
+
+    /// Specify in the number of terabytes, counted as multiples of 1024 gigabytes
+    pub const fn from_terabytes(terabytes: u64) -> Self {
+        Self::from_tebibytes(terabytes)
     }
 
     /// Get raw byte count in native representation
     pub fn as_bytes(self) -> u64 {
         self.0
     }
+
+    /// Add two sizes, failing on overflow
+    pub fn checked_add(self, other: Self) -> Result<Self, MemSizeError> {
+        self.0.checked_add(other.0).map(Self).ok_or(MemSizeError::Overflow)
+    }
+
+    /// Subtract a size, failing if the result would be negative
+    pub fn checked_sub(self, other: Self) -> Result<Self, MemSizeError> {
+        self.0.checked_sub(other.0).map(Self).ok_or(MemSizeError::Overflow)
+    }
+
+    /// Multiply the size by given factor, failing on overflow
+    pub fn checked_mul(self, factor: u64) -> Result<Self, MemSizeError> {
+        self.0.checked_mul(factor).map(Self).ok_or(MemSizeError::Overflow)
+    }
+}
+
+/// Number of bytes in given unit, matched case-insensitively
+fn unit_size(unit: &str) -> Result<u64, MemSizeError> {
+    if unit.is_empty() || unit.eq_ignore_ascii_case("B") {
+        return Ok(1);
+    }
+    DECIMAL_UNITS
+        .iter()
+        .chain(BINARY_UNITS.iter())
+        .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+        .map(|(_, size)| *size)
+        .ok_or_else(|| MemSizeError::UnknownUnit(unit.to_owned()))
+}
+
+/// Parse a size such as `4096`, `512MiB`, `2 GB` or `1.5GiB`.
+///
+/// Units are case-insensitive. `KB`, `MB`, `GB` and `TB` are multiples of 1000 bytes while
+/// `KiB`, `MiB`, `GiB` and `TiB` are multiples of 1024 bytes. A number without a unit is a
+/// number of bytes. Fractions of a byte are rounded down.
+impl FromStr for MemSize {
+    type Err = MemSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid_number = || MemSizeError::InvalidNumber(s.to_owned());
+
+        let number_len = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let (number, unit) = s.split_at(number_len);
+        let unit_size = unit_size(unit.trim_start())?;
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, "0"));
+        if whole.is_empty() || fraction.is_empty() || fraction.contains('.') {
+            return Err(invalid_number());
+        }
+
+        let whole = whole.parse::<u64>().map_err(|_| MemSizeError::Overflow)?;
+        let whole = whole.checked_mul(unit_size).ok_or(MemSizeError::Overflow)?;
+
+        // Fractions are evaluated exactly using integers, only the decimal places that could
+        // possibly make a difference are considered
+        let fraction = &fraction[..fraction.len().min(PARSE_DECIMALS)];
+        let scale = 10u128.pow(fraction.len() as u32);
+        let fraction = fraction.parse::<u128>().map_err(|_| invalid_number())?;
+        let fraction = u64::try_from(fraction * u128::from(unit_size) / scale)
+            .map_err(|_| MemSizeError::Overflow)?;
+
+        whole.checked_add(fraction).map(Self).ok_or(MemSizeError::Overflow)
+    }
 }
 
+/// Displays the size in the largest binary unit that represents it exactly with at most three
+/// decimal places, e.g. `512MiB` or `1.5GiB`, falling back to bytes.
 impl std::fmt::Display for MemSize {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scale = 10u64.pow(DISPLAY_DECIMALS);
+        for (name, unit_size) in BINARY_UNITS.iter().rev() {
+            let (whole, remainder) = (self.0 / unit_size, self.0 % unit_size);
+            if whole == 0 {
+                continue;
+            }
+            if remainder == 0 {
+                return write!(f, "{whole}{name}");
+            }
+            // The remainder is smaller than the unit which is small enough not to overflow
+            let fraction = remainder * scale;
+            if fraction % unit_size == 0 {
+                let fraction = format!(
+                    "{:0width$}",
+                    fraction / unit_size,
+                    width = DISPLAY_DECIMALS as usize
+                );
+                return write!(f, "{whole}.{}{name}", fraction.trim_end_matches('0'));
+            }
+        }
         write!(f, "{}B", self.0)
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MemSize {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes from a string parsed as by [FromStr] or from a number of bytes
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MemSize {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = MemSize;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a memory size such as \"512MiB\" or a number of bytes")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, bytes: u64) -> Result<MemSize, E> {
+                Ok(MemSize::from_bytes(bytes))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<MemSize, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let check = |s: &str, bytes: u64| {
+            assert_eq!(s.parse::<MemSize>(), Ok(MemSize::from_bytes(bytes)), "{s}");
+        };
+
+        check("0", 0);
+        check("4096", 4096);
+        check("4096B", 4096);
+        check(" 512MiB ", 512 << 20);
+        check("512 mib", 512 << 20);
+        check("2GB", 2_000_000_000);
+        check("1.5GiB", 3 << 29);
+        check("0.5KB", 500);
+        check("1.0009765625KiB", 1025);
+        check("1.1KiB", 1126);
+        check("16TiB", 16 << 40);
+        check("18446744073709551615", u64::MAX);
+        check("15.999999999999999999TiB", (16 << 40) - 1);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let check = |s: &str, err: MemSizeError| {
+            assert_eq!(s.parse::<MemSize>(), Err(err), "{s}");
+        };
+        let invalid = |s: &str| MemSizeError::InvalidNumber(s.to_owned());
+        let unknown = |s: &str| MemSizeError::UnknownUnit(s.to_owned());
+
+        check("", invalid(""));
+        check("MiB", invalid("MiB"));
+        check(".5MiB", invalid(".5MiB"));
+        check("5.MiB", invalid("5.MiB"));
+        check("1.2.3MiB", invalid("1.2.3MiB"));
+        check("5XB", unknown("XB"));
+        check("-5MiB", unknown("-5MiB"));
+        check("5 MiB extra", unknown("MiB extra"));
+        check("18446744073709551616", MemSizeError::Overflow);
+        check("16777216TiB", MemSizeError::Overflow);
+    }
+
+    #[test]
+    fn display() {
+        let check = |bytes: u64, s: &str| {
+            let size = MemSize::from_bytes(bytes);
+            assert_eq!(size.to_string(), s);
+            assert_eq!(s.parse(), Ok(size));
+        };
+
+        check(0, "0B");
+        check(1000, "1000B");
+        check(1024, "1KiB");
+        check(1280, "1.25KiB");
+        check(1025, "1025B");
+        check(1 << 30, "1GiB");
+        check(3 << 29, "1.5GiB");
+        check((1 << 30) + 1, "1073741825B");
+        check(1 << 50, "1024TiB");
+        check(u64::MAX, "18446744073709551615B");
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(MemSize::from_kibibytes(3).as_bytes(), 3 << 10);
+        assert_eq!(MemSize::from_mebibytes(3).as_bytes(), 3 << 20);
+        assert_eq!(MemSize::from_gibibytes(3).as_bytes(), 3 << 30);
+        assert_eq!(MemSize::from_tebibytes(3).as_bytes(), 3 << 40);
+        assert_eq!(MemSize::from_tebibytes(3), "3TiB".parse().unwrap());
+        assert_eq!(MemSize::from_kilobytes(3), MemSize::from_kibibytes(3));
+        assert_eq!(MemSize::from_megabytes(3), MemSize::from_mebibytes(3));
+        assert_eq!(MemSize::from_gigabytes(3), MemSize::from_gibibytes(3));
+        assert_eq!(MemSize::from_terabytes(3), MemSize::from_tebibytes(3));
+        assert_eq!(
+            MemSize::from_tebibytes(u64::MAX >> 40).as_bytes(),
+            (u64::MAX >> 40) << 40
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Memory size out of range")]
+    fn constructor_overflow() {
+        let _ = MemSize::from_tebibytes(1 << 24);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let one = MemSize::from_bytes(1);
+        let max = MemSize::from_bytes(u64::MAX);
+
+        assert_eq!(one.checked_add(one), Ok(MemSize::from_bytes(2)));
+        assert_eq!(max.checked_add(one), Err(MemSizeError::Overflow));
+        assert_eq!(max.checked_sub(one), Ok(MemSize::from_bytes(u64::MAX - 1)));
+        assert_eq!(MemSize::ZERO.checked_sub(one), Err(MemSizeError::Overflow));
+        assert_eq!(
+            MemSize::from_mebibytes(3).checked_mul(1024),
+            Ok(MemSize::from_gibibytes(3))
+        );
+        assert_eq!(max.checked_mul(2), Err(MemSizeError::Overflow));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        use serde::de::{value::Error, Deserialize, IntoDeserializer};
+
+        let from_str = MemSize::deserialize("1.5GiB".into_deserializer());
+        assert_eq!(from_str, Ok::<_, Error>(MemSize::from_bytes(3 << 29)));
+        let from_int = MemSize::deserialize(4096u64.into_deserializer());
+        assert_eq!(from_int, Ok::<_, Error>(MemSize::from_bytes(4096)));
+        let invalid: Result<_, Error> = MemSize::deserialize("5XB".into_deserializer());
+        assert!(invalid.is_err());
+    }
+}
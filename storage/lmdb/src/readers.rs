@@ -0,0 +1,112 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Maintenance of the reader lock table
+//!
+//! Each read-only transaction occupies a slot in the reader lock table shared by all processes
+//! using the database. Slots of processes that have been killed are not released, making the
+//! table fill up over time until starting a read-only transaction fails.
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clear the reader slots of processes that are no longer running, returning the number of slots
+/// cleared
+pub fn check(env: &lmdb::Environment) -> Result<usize, lmdb::Error> {
+    let mut dead: c_int = 0;
+    // SAFETY: The environment is open for as long as it is borrowed and the count is written
+    // into a local variable.
+    let rc = unsafe { lmdb_sys::mdb_reader_check(env.env(), &mut dead) };
+    match rc {
+        0 => Ok(usize::try_from(dead).expect("Negative number of stale readers")),
+        rc => Err(lmdb::Error::from_err_code(rc)),
+    }
+}
+
+/// Human-readable dump of the reader lock table, one line per used slot after a header
+pub fn list(env: &lmdb::Environment) -> Result<String, lmdb::Error> {
+    unsafe extern "C" fn append(msg: *const c_char, ctx: *mut c_void) -> c_int {
+        // SAFETY: LMDB passes a NUL-terminated message along with the context given below
+        let (msg, out) = unsafe { (CStr::from_ptr(msg), &mut *ctx.cast::<String>()) };
+        out.push_str(&msg.to_string_lossy());
+        0
+    }
+
+    let mut out = String::new();
+    // SAFETY: The environment is open for as long as it is borrowed. The callback is only
+    // invoked during the call and the context points to a local variable of the type the
+    // callback expects.
+    let rc = unsafe {
+        lmdb_sys::mdb_reader_list(
+            env.env(),
+            Some(append),
+            (&mut out as *mut String).cast::<c_void>(),
+        )
+    };
+    match rc {
+        rc if rc >= 0 => Ok(out),
+        rc => Err(lmdb::Error::from_err_code(rc)),
+    }
+}
+
+/// Keeps track of the stale reader checks run automatically
+pub struct AutoCheck {
+    /// How often to check for stale readers, if at all
+    interval: Option<Duration>,
+
+    /// Time of the last check
+    last_check: Mutex<Instant>,
+
+    /// Total number of stale reader slots cleared
+    cleared: AtomicUsize,
+}
+
+impl AutoCheck {
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last_check: Mutex::new(Instant::now()),
+            cleared: AtomicUsize::new(0),
+        }
+    }
+
+    /// Run the check unconditionally, e.g. when the database is opened
+    pub fn run(&self, env: &lmdb::Environment) -> Result<usize, lmdb::Error> {
+        *self.last_check.lock().expect("Poisoned mutex") = Instant::now();
+        let cleared = check(env)?;
+        self.cleared.fetch_add(cleared, Ordering::Relaxed);
+        Ok(cleared)
+    }
+
+    /// Run the check if the interval has elapsed since the last one
+    pub fn run_if_due(&self, env: &lmdb::Environment) -> Result<(), lmdb::Error> {
+        if let Some(interval) = self.interval {
+            let mut last_check = self.last_check.lock().expect("Poisoned mutex");
+            if last_check.elapsed() >= interval {
+                *last_check = Instant::now();
+                drop(last_check);
+                self.cleared.fetch_add(check(env)?, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Total number of stale reader slots cleared by the checks so far
+    pub fn cleared(&self) -> usize {
+        self.cleared.load(Ordering::Relaxed)
+    }
+}
@@ -13,7 +13,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use lmdb::DatabaseResizeInfo;
+use crate::stats::permille;
+
+/// Information about a resize of the memory map, passed to the resize callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseResizeInfo {
+    /// Size of the memory map in bytes before the resize
+    pub old_size: u64,
+    /// Size of the memory map in bytes after the resize
+    pub new_size: u64,
+    /// Number of bytes of the memory map used by the database
+    pub occupied_size: u64,
+    /// Share of the memory map used by the database before the resize, in per-mille
+    pub fill_permille_before: u64,
+    /// Share of the memory map used by the database after the resize, in per-mille
+    pub fill_permille_after: u64,
+}
+
+impl DatabaseResizeInfo {
+    pub(crate) fn from_lmdb(info: lmdb::DatabaseResizeInfo) -> Self {
+        Self {
+            old_size: info.old_size,
+            new_size: info.new_size,
+            occupied_size: info.occupied_size,
+            fill_permille_before: permille(info.occupied_size, info.old_size),
+            fill_permille_after: permille(info.occupied_size, info.new_size),
+        }
+    }
+}
 
 pub type MapResizeCallbackFn = dyn Fn(DatabaseResizeInfo);
 
@@ -31,6 +58,12 @@ impl MapResizeCallback {
     pub fn take(self) -> Option<Box<MapResizeCallbackFn>> {
         self.f
     }
+
+    /// Turn into the callback taken by the underlying library, which passes its own resize info
+    pub(crate) fn into_lmdb(self) -> Option<Box<dyn Fn(lmdb::DatabaseResizeInfo)>> {
+        let f = self.f?;
+        Some(Box::new(move |info| f(DatabaseResizeInfo::from_lmdb(info))))
+    }
 }
 
 impl From<MapResizeCallback> for Option<Box<MapResizeCallbackFn>> {
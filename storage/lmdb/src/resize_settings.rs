@@ -0,0 +1,177 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Settings for growing the LMDB memory map, in a form suitable for configuration
+
+use std::str::FromStr;
+
+use crate::memsize::{MemSize, MemSizeError};
+use lmdb::DatabaseResizeSettings;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone)]
+pub enum ResizeSettingsError {
+    #[error("Invalid resize setting, expected 'name=value': '{0}'")]
+    InvalidEntry(String),
+    #[error("Unknown resize setting: '{0}'")]
+    UnknownSetting(String),
+    #[error("Invalid value of resize setting {0}: '{1}'")]
+    InvalidValue(String, String),
+    #[error("Invalid resize step: {0}")]
+    InvalidStep(#[from] MemSizeError),
+}
+
+/// Map resize settings, converted into [DatabaseResizeSettings] to be used by the backend.
+///
+/// Settings missing when deserializing or parsing take their default values.
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ResizeSettings {
+    /// The smallest amount the map grows by
+    pub min_resize_step: MemSize,
+
+    /// The largest amount the map grows by
+    pub max_resize_step: MemSize,
+
+    /// How much the map grows by, as a percentage of its current size within the step limits
+    pub resize_ratio_percentage: u16,
+
+    /// Share of the map that has to be in use for it to grow, between 0 and 1
+    pub resize_trigger_percentage: f32,
+}
+
+impl Default for ResizeSettings {
+    fn default() -> Self {
+        let settings = DatabaseResizeSettings::default();
+        Self {
+            min_resize_step: MemSize::from_bytes(settings.min_resize_step as u64),
+            max_resize_step: MemSize::from_bytes(settings.max_resize_step as u64),
+            resize_ratio_percentage: u16::try_from(settings.default_resize_ratio_percentage)
+                .unwrap_or(u16::MAX),
+            resize_trigger_percentage: settings.resize_trigger_percentage,
+        }
+    }
+}
+
+impl From<ResizeSettings> for DatabaseResizeSettings {
+    fn from(settings: ResizeSettings) -> Self {
+        Self {
+            min_resize_step: settings.min_resize_step.as_bytes() as usize,
+            max_resize_step: settings.max_resize_step.as_bytes() as usize,
+            default_resize_ratio_percentage: settings.resize_ratio_percentage.into(),
+            resize_trigger_percentage: settings.resize_trigger_percentage,
+        }
+    }
+}
+
+/// Parse comma-separated settings such as `min_resize_step=16MiB,resize_trigger_percentage=0.8`
+/// using the field names. The steps are parsed as [MemSize].
+impl FromStr for ResizeSettings {
+    type Err = ResizeSettingsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| ResizeSettingsError::InvalidEntry(entry.to_owned()))?;
+            let (name, value) = (name.trim(), value.trim());
+            let invalid_value =
+                || ResizeSettingsError::InvalidValue(name.to_owned(), value.to_owned());
+
+            match name {
+                "min_resize_step" => settings.min_resize_step = value.parse()?,
+                "max_resize_step" => settings.max_resize_step = value.parse()?,
+                "resize_ratio_percentage" => {
+                    settings.resize_ratio_percentage = value.parse().map_err(|_| invalid_value())?
+                }
+                "resize_trigger_percentage" => {
+                    settings.resize_trigger_percentage =
+                        value.parse().map_err(|_| invalid_value())?
+                }
+                _ => return Err(ResizeSettingsError::UnknownSetting(name.to_owned())),
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("".parse(), Ok(ResizeSettings::default()));
+
+        let expected = ResizeSettings {
+            min_resize_step: MemSize::from_mebibytes(16),
+            max_resize_step: MemSize::from_gibibytes(1),
+            resize_ratio_percentage: 20,
+            resize_trigger_percentage: 0.8,
+        };
+        let spec = "min_resize_step=16MiB, max_resize_step = 1GiB,resize_ratio_percentage=20,\
+            resize_trigger_percentage=0.8";
+        assert_eq!(spec.parse(), Ok(expected));
+
+        let partial = ResizeSettings {
+            min_resize_step: MemSize::from_mebibytes(16),
+            ..ResizeSettings::default()
+        };
+        assert_eq!("min_resize_step=16MiB".parse(), Ok(partial));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let check = |s: &str, err: ResizeSettingsError| {
+            assert_eq!(s.parse::<ResizeSettings>(), Err(err), "{s}");
+        };
+
+        check(
+            "min_resize_step",
+            ResizeSettingsError::InvalidEntry("min_resize_step".to_owned()),
+        );
+        check(
+            "resize_step=1MiB",
+            ResizeSettingsError::UnknownSetting("resize_step".to_owned()),
+        );
+        check(
+            "resize_ratio_percentage=-5",
+            ResizeSettingsError::InvalidValue(
+                "resize_ratio_percentage".to_owned(),
+                "-5".to_owned(),
+            ),
+        );
+        check(
+            "max_resize_step=5XB",
+            ResizeSettingsError::InvalidStep(MemSizeError::UnknownUnit("XB".to_owned())),
+        );
+    }
+
+    #[test]
+    fn into_database_resize_settings() {
+        let settings: DatabaseResizeSettings = "min_resize_step=64KiB,max_resize_step=1MiB"
+            .parse::<ResizeSettings>()
+            .unwrap()
+            .into();
+        assert_eq!(settings.min_resize_step, 1 << 16);
+        assert_eq!(settings.max_resize_step, 1 << 20);
+    }
+}
@@ -107,6 +107,7 @@ fn auto_map_resize_between_txs(#[case] seed: Seed) {
             assert!(act.old_size < act.new_size);
             assert!(act.new_size - act.old_size >= resize_settings.min_resize_step as u64);
             assert!(act.new_size - act.old_size <= resize_settings.max_resize_step as u64);
+            assert!(act.fill_permille_before > act.fill_permille_after);
         }
 
         // ensure data is successfully written
@@ -202,6 +203,7 @@ fn auto_map_resize_between_puts(#[case] seed: Seed) {
             assert!(act.old_size < act.new_size);
             assert!(act.new_size - act.old_size >= resize_settings.min_resize_step as u64);
             assert!(act.new_size - act.old_size <= resize_settings.max_resize_step as u64);
+            assert!(act.fill_permille_before > act.fill_permille_after);
         }
 
         // ensure data is successfully written
@@ -216,3 +218,124 @@ fn auto_map_resize_between_puts(#[case] seed: Seed) {
     );
     })
 }
+
+// LMDB refuses to change the map size while a read-write transaction is active, which is used
+// below to make resizes fail
+fn open_lmdb_for_resize_failure(data_dir: &std::path::Path) -> LmdbImpl {
+    let lmdb = Lmdb::new(
+        data_dir.to_owned(),
+        MemSize::from_bytes(1 << 20).into(),
+        DatabaseResizeSettings::default(),
+        MapResizeCallback::default(),
+    );
+    let desc = storage_core::types::construct::db_desc([DbMapDesc::new("SomeDb")].into_iter());
+    lmdb.open(desc).unwrap()
+}
+
+#[test]
+fn failed_resize_reported() {
+    utils::concurrency::model(move || {
+        let data_dir = tempfile::Builder::new().prefix("lmdb_resize").tempdir().unwrap();
+        let lmdb_impl = open_lmdb_for_resize_failure(data_dir.path());
+        let map_size = || lmdb_impl.env.info().unwrap().map_size();
+        let initial_map_size = map_size();
+
+        // The resize is retried, then the error of the last attempt is reported
+        let rw_tx = lmdb_impl.transaction_rw(None).unwrap();
+        lmdb_impl.schedule_map_resize();
+        let err = lmdb_impl.transaction_rw(None).err().unwrap();
+        assert!(
+            matches!(
+                err,
+                storage_core::Error::Fatal(storage_core::error::Fatal::Io(
+                    std::io::ErrorKind::InvalidInput,
+                    _
+                ))
+            ),
+            "unexpected error {err:?}"
+        );
+        assert_eq!(map_size(), initial_map_size);
+        drop(rw_tx);
+
+        // The resize is still scheduled and it succeeds once nothing stands in its way
+        let mut rw_tx = lmdb_impl.transaction_rw(None).unwrap();
+        assert!(map_size() > initial_map_size);
+        rw_tx.put(DbMapId::new(0), b"key".to_vec(), b"val".to_vec()).unwrap();
+        rw_tx.commit().unwrap();
+
+        let ro_tx = lmdb_impl.transaction_ro().unwrap();
+        assert_eq!(
+            ro_tx.get(DbMapId::new(0), b"key").unwrap().as_deref(),
+            Some(&b"val"[..])
+        );
+    })
+}
+
+#[test]
+fn resize_errors() {
+    use storage_core::error::{Fatal, Recoverable};
+
+    let out_of_space = lmdb::Error::Other(error::OUT_OF_SPACE_CODES[0]);
+    let result = error::process_resize_error::<()>(out_of_space);
+    assert_eq!(result, Err(Fatal::OutOfSpace.into()));
+
+    let result = error::process_resize_error::<()>(lmdb::Error::MapFull);
+    assert_eq!(result, Err(Recoverable::TemporarilyUnavailable.into()));
+}
+
+#[test]
+fn map_resized_ahead_of_large_transaction() {
+    utils::concurrency::model(move || {
+        let resize_actions = Arc::new(Mutex::new(Vec::new()));
+        let resize_actions_for_check = Arc::clone(&resize_actions);
+        let resize_callback = Box::new(move |v| resize_actions.lock().unwrap().push(v));
+
+        let initial_map_size = 1 << 20;
+        let resize_settings = DatabaseResizeSettings {
+            min_resize_step: 1 << 16,
+            max_resize_step: 1 << 20,
+            default_resize_ratio_percentage: 10,
+            resize_trigger_percentage: 0.9,
+        };
+
+        let data_dir = tempfile::Builder::new().prefix("lmdb_resize").tempdir().unwrap();
+        let lmdb = Lmdb::new(
+            data_dir.path().to_owned(),
+            MemSize::from_bytes(initial_map_size).into(),
+            resize_settings.clone(),
+            MapResizeCallback::new(resize_callback),
+        );
+        let desc = storage_core::types::construct::db_desc([DbMapDesc::new("SomeDb")].into_iter());
+        let lmdb_impl = lmdb.open(desc).unwrap();
+
+        // A small transaction fits in the map as it is
+        let rw_tx = lmdb_impl.transaction_rw(Some(1 << 10)).unwrap();
+        drop(rw_tx);
+        assert!(resize_actions_for_check.lock().unwrap().is_empty());
+
+        // A large one makes the map grow in multiple steps before it starts
+        let value_count = 2 << 10;
+        let value_size = 1 << 10;
+        let mut rw_tx = lmdb_impl.transaction_rw(Some(4 * value_count * value_size)).unwrap();
+        let resize_actions = resize_actions_for_check.lock().unwrap().clone();
+        assert!(resize_actions.len() > 1);
+        for act in &resize_actions {
+            assert!(act.new_size - act.old_size >= resize_settings.min_resize_step as u64);
+            assert!(act.new_size - act.old_size <= resize_settings.max_resize_step as u64);
+        }
+        let map_size = lmdb_impl.env.info().unwrap().map_size();
+        assert!(map_size >= 4 * value_count * value_size);
+
+        // The transaction then succeeds without resizing the map any further
+        for i in 0..value_count {
+            let key = (i as u32).to_be_bytes().to_vec();
+            rw_tx.put(DbMapId::new(0), key, vec![0x5a; value_size]).unwrap();
+        }
+        rw_tx.commit().unwrap();
+        assert_eq!(
+            resize_actions_for_check.lock().unwrap().len(),
+            resize_actions.len()
+        );
+        assert_eq!(lmdb_impl.env.info().unwrap().map_size(), map_size);
+    })
+}
@@ -0,0 +1,85 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics of the LMDB environment and its database maps
+
+use std::collections::BTreeMap;
+
+/// Share of `total` taken up by `used`, in per-mille rounded down
+pub(crate) fn permille(used: u64, total: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    let permille = u128::from(used) * 1000 / u128::from(total);
+    permille.try_into().unwrap_or(u64::MAX)
+}
+
+/// B-tree statistics of a database map, see `mdb_stat`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapStats {
+    /// Depth (height) of the B-tree
+    pub depth: u32,
+    /// Number of internal (non-leaf) pages
+    pub branch_pages: usize,
+    /// Number of leaf pages
+    pub leaf_pages: usize,
+    /// Number of overflow pages
+    pub overflow_pages: usize,
+    /// Number of data items
+    pub entries: usize,
+}
+
+impl MapStats {
+    pub(crate) fn from_lmdb(stat: &lmdb::Stat) -> Self {
+        Self {
+            depth: stat.depth(),
+            branch_pages: stat.branch_pages(),
+            leaf_pages: stat.leaf_pages(),
+            overflow_pages: stat.overflow_pages(),
+            entries: stat.entries(),
+        }
+    }
+}
+
+/// Statistics of the environment, see `mdb_env_info`, with statistics of each database map
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvStats {
+    /// Size of the memory map in bytes
+    pub map_size: usize,
+    /// Size of a database page in bytes
+    pub page_size: u32,
+    /// Number of the last page used
+    pub last_page_number: usize,
+    /// ID of the last committed transaction
+    pub last_transaction_id: usize,
+    /// Maximum number of reader slots
+    pub max_readers: u32,
+    /// Number of reader slots used
+    pub num_readers: u32,
+    /// Statistics of each database map, keyed by the map name
+    pub maps: BTreeMap<String, MapStats>,
+}
+
+impl EnvStats {
+    /// Number of bytes of the memory map used by the database
+    pub fn occupied_size(&self) -> usize {
+        (self.last_page_number + 1) * self.page_size as usize
+    }
+
+    /// Share of the memory map used by the database, in per-mille
+    pub fn fill_permille(&self) -> u64 {
+        permille(self.occupied_size() as u64, self.map_size as u64)
+    }
+}
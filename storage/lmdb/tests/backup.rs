@@ -0,0 +1,138 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for copying and compacting the database
+
+use std::path::{Path, PathBuf};
+
+use storage_backend_test_suite::prelude::*;
+use storage_lmdb::{Lmdb, LmdbImpl};
+
+fn lmdb(path: PathBuf) -> Lmdb {
+    Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+}
+
+fn key(i: u32) -> Data {
+    i.to_be_bytes().to_vec()
+}
+
+/// Fill the database, then delete most of it, leaving plenty of free pages behind
+fn fill_and_prune(store: &LmdbImpl) {
+    let mut dbtx = store.transaction_rw(Some(2 << 20)).unwrap();
+    for i in 0..1000 {
+        dbtx.put(MAPID.0, key(i), vec![0x5a; 1000]).unwrap();
+    }
+    dbtx.commit().unwrap();
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for i in 10..1000 {
+        dbtx.del(MAPID.0, &key(i)).unwrap();
+    }
+    dbtx.commit().unwrap();
+}
+
+fn check_pruned(store: &LmdbImpl) {
+    let dbtx = store.transaction_ro().unwrap();
+    let entries = dbtx.prefix_iter(MAPID.0, Data::new()).unwrap().map(owned).collect::<Vec<_>>();
+    let expected = (0..10).map(|i| (key(i), vec![0x5a; 1000])).collect::<Vec<_>>();
+    assert_eq!(entries, expected);
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+#[test]
+fn hot_backup() {
+    let test_root = test_utils::test_root!("backup-tests").unwrap();
+    let path = test_root.fresh_test_dir("db").as_ref().to_path_buf();
+    let copy_path = test_root.fresh_test_dir("copy").as_ref().to_path_buf();
+    let compact_path = test_root.fresh_test_dir("compact").as_ref().join("nested");
+
+    let store = lmdb(path.clone()).open(desc(1)).unwrap();
+    fill_and_prune(&store);
+
+    let copy_stats = store.backup(&copy_path, false).unwrap();
+    assert_eq!(copy_stats.size_before, file_size(&path.join("data.mdb")));
+    assert_eq!(
+        copy_stats.size_after,
+        file_size(&copy_path.join("data.mdb"))
+    );
+
+    // The target directory is created if it does not exist yet
+    let compact_stats = store.backup(&compact_path, true).unwrap();
+    assert_eq!(compact_stats.size_before, copy_stats.size_before);
+    assert!(compact_stats.size_after < copy_stats.size_after);
+
+    // The original database stays usable
+    check_pruned(&store);
+    assert!(store.backup(&copy_path, false).is_err());
+    drop(store);
+
+    for path in [copy_path, compact_path] {
+        let copy = lmdb(path).open(desc(1)).unwrap();
+        check_pruned(&copy);
+    }
+
+    test_root.delete();
+}
+
+#[test]
+fn compact_in_place() {
+    let test_root = test_utils::test_root!("backup-tests").unwrap();
+    let path = test_root.fresh_test_dir("db").as_ref().to_path_buf();
+
+    let store = lmdb(path.clone()).open(desc(1)).unwrap();
+    fill_and_prune(&store);
+    drop(store);
+
+    let stats = lmdb(path.clone()).compact_in_place().unwrap();
+    assert!(stats.size_after < stats.size_before);
+    assert_eq!(stats.size_after, file_size(&path.join("data.mdb")));
+    assert!(!path.join("compact").exists());
+
+    let store = lmdb(path).open(desc(1)).unwrap();
+    check_pruned(&store);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn compact_in_place_no_sub_dir() {
+    let test_root = test_utils::test_root!("backup-tests").unwrap();
+    let dir = test_root.fresh_test_dir("db").as_ref().to_path_buf();
+    let path = dir.join("database.mdb");
+
+    let store = lmdb(path.clone()).with_no_sub_dir().open(desc(1)).unwrap();
+    fill_and_prune(&store);
+    drop(store);
+
+    let stats = lmdb(path.clone()).with_no_sub_dir().compact_in_place().unwrap();
+    assert!(stats.size_after < stats.size_before);
+    assert_eq!(stats.size_after, file_size(&path));
+    assert!(!dir.join("database.mdb.compact").exists());
+
+    let store = lmdb(path).with_no_sub_dir().open(desc(1)).unwrap();
+    check_pruned(&store);
+
+    drop(store);
+    test_root.delete();
+}
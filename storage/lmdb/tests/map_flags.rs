@@ -0,0 +1,301 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the key order and duplicate values of database maps
+
+use std::ops::Bound;
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::{error::Fatal, KeyOrder};
+use storage_lmdb::Lmdb;
+
+fn lmdb(path: std::path::PathBuf) -> Lmdb {
+    Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+}
+
+fn single_map_desc(map_desc: DbMapDesc) -> DbDesc {
+    storage_core::types::construct::db_desc([map_desc].into_iter())
+}
+
+fn all_keys(store: &impl BackendImpl) -> Vec<Data> {
+    let dbtx = store.transaction_ro().unwrap();
+    let keys = dbtx.prefix_iter_keys(MAPID.0, Data::new()).unwrap();
+    keys.map(owned_key).collect()
+}
+
+#[test]
+fn key_order() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+
+    let check = |key_order: KeyOrder, keys: &[Data], expected: &[Data]| {
+        let path = test_root.fresh_test_dir(format!("{key_order:?}")).as_ref().to_path_buf();
+        let desc = single_map_desc(DbMapDesc::new("map").with_key_order(key_order));
+        let store = lmdb(path).open(desc).unwrap();
+
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        for key in keys {
+            dbtx.put(MAPID.0, key.clone(), b"val".to_vec()).unwrap();
+        }
+        dbtx.commit().unwrap();
+        assert_eq!(all_keys(&store), expected);
+    };
+
+    let keys = [b"ba".to_vec(), b"ab".to_vec(), b"aa".to_vec()];
+    check(
+        KeyOrder::Lexicographic,
+        &keys,
+        &[b"aa".to_vec(), b"ab".to_vec(), b"ba".to_vec()],
+    );
+    check(
+        KeyOrder::Reverse,
+        &keys,
+        &[b"aa".to_vec(), b"ba".to_vec(), b"ab".to_vec()],
+    );
+
+    let numbers = [256u32, 1, 65536, 2];
+    let keys = numbers.map(|n| n.to_ne_bytes().to_vec());
+    let expected = [1u32, 2, 256, 65536].map(|n| n.to_ne_bytes().to_vec());
+    check(KeyOrder::Integer, &keys, &expected);
+
+    test_root.delete();
+}
+
+fn populated(path: std::path::PathBuf, key_order: KeyOrder, keys: &[Data]) -> impl BackendImpl {
+    let desc = single_map_desc(DbMapDesc::new("map").with_key_order(key_order));
+    let store = lmdb(path).open(desc).unwrap();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for key in keys {
+        dbtx.put(MAPID.0, key.clone(), b"val".to_vec()).unwrap();
+    }
+    dbtx.commit().unwrap();
+    store
+}
+
+#[test]
+fn ranges_in_key_order() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+
+    let check = |key_order: KeyOrder, keys: &[Data], range: (Data, Data), expected: &[Data]| {
+        let path = test_root.fresh_test_dir(format!("{key_order:?}")).as_ref().to_path_buf();
+        let store = populated(path, key_order, keys);
+        let (start, end) = (Bound::Included(range.0), Bound::Excluded(range.1));
+
+        let dbtx = store.transaction_ro().unwrap();
+        let iter = dbtx.range_iter(MAPID.0, start.clone(), end.clone()).unwrap();
+        assert_eq!(iter.map(|item| owned(item).0).collect::<Vec<_>>(), expected);
+        let iter = dbtx.range_iter_rev(MAPID.0, start.clone(), end.clone()).unwrap();
+        let mut expected_rev = expected.to_vec();
+        expected_rev.reverse();
+        assert_eq!(
+            iter.map(|item| owned(item).0).collect::<Vec<_>>(),
+            expected_rev
+        );
+        drop(dbtx);
+
+        // Deleting the range leaves the keys outside of it in the same order
+        let mut remaining = all_keys(&store);
+        remaining.retain(|key| !expected.contains(key));
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        dbtx.del_range(MAPID.0, start, end).unwrap();
+        dbtx.commit().unwrap();
+        assert_eq!(all_keys(&store), remaining);
+    };
+
+    // Ordered by the last byte first: aa, ba, ab, bb
+    let keys = [b"bb".to_vec(), b"ab".to_vec(), b"ba".to_vec(), b"aa".to_vec()];
+    check(
+        KeyOrder::Reverse,
+        &keys,
+        (b"ba".to_vec(), b"bb".to_vec()),
+        &[b"ba".to_vec(), b"ab".to_vec()],
+    );
+
+    let keys = [65536u32, 1, 256, 2].map(|n| n.to_ne_bytes().to_vec());
+    let range = (2u32.to_ne_bytes().to_vec(), 65536u32.to_ne_bytes().to_vec());
+    let expected = [2u32, 256].map(|n| n.to_ne_bytes().to_vec());
+    check(KeyOrder::Integer, &keys, range, &expected);
+
+    test_root.delete();
+}
+
+#[test]
+fn unsupported_selections() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+
+    for key_order in [KeyOrder::Reverse, KeyOrder::Integer] {
+        let path = test_root.fresh_test_dir(format!("{key_order:?}")).as_ref().to_path_buf();
+        let keys = [1u32, 2].map(|n| n.to_ne_bytes().to_vec());
+        let store = populated(path, key_order, &keys);
+
+        // Keys sharing a prefix are not adjacent, only the whole map can be selected
+        let expected = Fatal::UnsupportedOperation(format!(
+            "Prefix selection in map map with {key_order:?} key order"
+        ));
+        let dbtx = store.transaction_ro().unwrap();
+        let result = dbtx.prefix_iter(MAPID.0, vec![1]).map(|_| ());
+        assert_eq!(result, Err(expected.clone().into()));
+        let result = dbtx.prefix_iter_rev(MAPID.0, vec![1]).map(|_| ());
+        assert_eq!(result, Err(expected.clone().into()));
+        assert_eq!(dbtx.prefix_iter(MAPID.0, Data::new()).unwrap().count(), 2);
+        assert_eq!(
+            dbtx.prefix_iter_rev(MAPID.0, Data::new()).unwrap().count(),
+            2
+        );
+        drop(dbtx);
+
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        assert_eq!(dbtx.del_prefix(MAPID.0, vec![1]), Err(expected.into()));
+        dbtx.del_prefix(MAPID.0, Data::new()).unwrap();
+        dbtx.commit().unwrap();
+        assert_eq!(all_keys(&store), Vec::<Data>::new());
+    }
+
+    // Range bounds in maps with integer keys have to be integers too
+    let path = test_root.fresh_test_dir("integer_bounds").as_ref().to_path_buf();
+    let store = populated(path, KeyOrder::Integer, &[]);
+    let dbtx = store.transaction_ro().unwrap();
+    let result = dbtx.range_iter(MAPID.0, Bound::Included(vec![1, 2]), Bound::Unbounded);
+    let expected =
+        Fatal::UnsupportedOperation("Range bound of 2 bytes in map map with integer keys".into());
+    assert_eq!(result.map(|_| ()), Err(expected.into()));
+
+    test_root.delete();
+}
+
+#[test]
+fn duplicate_values() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+
+    for value_size_hint in [0..usize::MAX, 2..3] {
+        let path = test_root.fresh_test_dir("duplicates").as_ref().to_path_buf();
+        let map_desc = DbMapDesc::new_with_details("map", value_size_hint).with_duplicate_values();
+        let store = lmdb(path).open(single_map_desc(map_desc)).unwrap();
+
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        for (key, val) in [(b"k1", b"v2"), (b"k1", b"v1"), (b"k2", b"v3"), (b"k1", b"v2")] {
+            dbtx.put(MAPID.0, key.to_vec(), val.to_vec()).unwrap();
+        }
+        dbtx.commit().unwrap();
+
+        // Each distinct value is kept, sorted, reading a key gives the smallest one
+        let dbtx = store.transaction_ro().unwrap();
+        let entries = dbtx.prefix_iter(MAPID.0, Data::new()).unwrap().map(owned);
+        let expected = [(b"k1", b"v1"), (b"k1", b"v2"), (b"k2", b"v3")]
+            .map(|(key, val)| (key.to_vec(), val.to_vec()));
+        assert_eq!(entries.collect::<Vec<_>>(), expected);
+        assert_eq!(
+            dbtx.get(MAPID.0, b"k1").unwrap().as_deref(),
+            Some(&b"v1"[..])
+        );
+
+        // Iterating backwards gives all values of a key, including one at the end bound
+        let rev_range = |start: Bound<Data>, end: Bound<Data>| {
+            let entries = dbtx.range_iter_rev(MAPID.0, start, end).unwrap().map(owned);
+            entries.collect::<Vec<_>>()
+        };
+        let (k1, k2) = (b"k1".to_vec(), b"k2".to_vec());
+        let mut expected_rev = expected.to_vec();
+        expected_rev.reverse();
+        assert_eq!(rev_range(Bound::Unbounded, Bound::Unbounded), expected_rev);
+        assert_eq!(
+            rev_range(Bound::Unbounded, Bound::Included(k1.clone())),
+            expected_rev[1..]
+        );
+        assert_eq!(
+            rev_range(Bound::Included(k1), Bound::Included(k2.clone())),
+            expected_rev
+        );
+        assert_eq!(
+            rev_range(Bound::Unbounded, Bound::Excluded(k2)),
+            expected_rev[1..]
+        );
+        drop(dbtx);
+
+        // Deleting a key removes all of its values
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        dbtx.del(MAPID.0, b"k1").unwrap();
+        dbtx.commit().unwrap();
+        assert_eq!(all_keys(&store), [b"k2".to_vec()]);
+    }
+
+    test_root.delete();
+}
+
+fn all_values(dbtx: &impl ReadOps) -> Vec<Data> {
+    let entries = dbtx.prefix_iter(MAPID.0, Data::new()).unwrap();
+    entries.map(|item| owned(item).1).collect()
+}
+
+#[test]
+fn conditional_writes_with_duplicates() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+    let path = test_root.fresh_test_dir("conditional").as_ref().to_path_buf();
+    let map_desc = DbMapDesc::new("map").with_duplicate_values();
+    let store = lmdb(path).open(single_map_desc(map_desc)).unwrap();
+
+    let key = || b"key".to_vec();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, key(), b"v1".to_vec()).unwrap();
+    dbtx.put(MAPID.0, key(), b"v2".to_vec()).unwrap();
+
+    // Only a value the key holds can be swapped, the other values are kept
+    let mut swap = |expected: Option<&[u8]>, new: Option<&[u8]>| {
+        let swapped = dbtx
+            .compare_and_swap(MAPID.0, key(), expected, new.map(<[u8]>::to_vec))
+            .unwrap();
+        (swapped, all_values(&dbtx))
+    };
+    assert_eq!(
+        swap(Some(b"v3"), Some(b"v4")),
+        (false, vec![b"v1".to_vec(), b"v2".to_vec()])
+    );
+    assert_eq!(
+        swap(Some(b"v2"), Some(b"v4")),
+        (true, vec![b"v1".to_vec(), b"v4".to_vec()])
+    );
+    assert_eq!(swap(Some(b"v1"), None), (true, vec![b"v4".to_vec()]));
+
+    // Writing to a key with no value fails if it holds any value
+    assert_eq!(swap(None, Some(b"v5")), (false, vec![b"v4".to_vec()]));
+    assert_eq!(swap(Some(b"v4"), None), (true, vec![]));
+    assert_eq!(swap(None, Some(b"v5")), (true, vec![b"v5".to_vec()]));
+    assert!(!dbtx.put_if_absent(MAPID.0, key(), b"v6".to_vec()).unwrap());
+    dbtx.commit().unwrap();
+
+    test_root.delete();
+}
+
+#[test]
+fn changed_map_layout() {
+    let test_root = test_utils::test_root!("map-flags-tests").unwrap();
+    let path = test_root.fresh_test_dir("changed").as_ref().to_path_buf();
+
+    let map_desc = DbMapDesc::new("map").with_key_order(KeyOrder::Reverse);
+    let store = lmdb(path.clone()).open(single_map_desc(map_desc)).unwrap();
+    drop(store);
+
+    let result = lmdb(path).open(single_map_desc(
+        DbMapDesc::new("map").with_duplicate_values(),
+    ));
+    let expected = Fatal::SchemaMismatch("Map map has been created with a different layout".into());
+    assert_eq!(result.err(), Some(expected.into()));
+
+    test_root.delete();
+}
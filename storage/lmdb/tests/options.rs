@@ -0,0 +1,185 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the environment settings
+
+use std::{path::PathBuf, time::Duration};
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::error::{Fatal, Recoverable};
+use storage_lmdb::Lmdb;
+
+fn lmdb(path: PathBuf) -> Lmdb {
+    Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+}
+
+fn put_and_get(store: &impl BackendImpl) {
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"key".to_vec(), b"val".to_vec()).unwrap();
+    dbtx.commit().unwrap();
+
+    let dbtx = store.transaction_ro().unwrap();
+    assert_eq!(
+        dbtx.get(MAPID.0, b"key").unwrap().as_deref(),
+        Some(&b"val"[..])
+    );
+}
+
+#[test]
+fn performance_flags() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let path = test_root.fresh_test_dir("flags").as_ref().to_path_buf();
+
+    let store = lmdb(path)
+        .with_write_map()
+        .with_map_async()
+        .with_no_sync()
+        .with_no_meta_sync()
+        .with_no_readahead()
+        .with_no_lock()
+        .open(desc(1))
+        .unwrap();
+    put_and_get(&store);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn max_readers_with_no_tls() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let path = test_root.fresh_test_dir("readers").as_ref().to_path_buf();
+
+    let store = lmdb(path).with_no_tls().with_max_readers(2).open(desc(1)).unwrap();
+    put_and_get(&store);
+
+    // Without thread-local reader slots, one thread can hold multiple read-only transactions
+    let dbtx1 = store.transaction_ro().unwrap();
+    let dbtx2 = store.transaction_ro().unwrap();
+    assert_eq!(
+        store.transaction_ro().err(),
+        Some(Recoverable::TemporarilyUnavailable.into())
+    );
+    drop(dbtx1);
+    let dbtx3 = store.transaction_ro().unwrap();
+    assert_eq!(
+        dbtx3.get(MAPID.0, b"key").unwrap().as_deref(),
+        Some(&b"val"[..])
+    );
+
+    drop((dbtx2, dbtx3));
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn no_sub_dir_layout() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let dir = test_root.fresh_test_dir("no_sub_dir").as_ref().join("nested");
+    let path = dir.join("database.mdb");
+
+    let store = lmdb(path.clone()).with_no_sub_dir().open(desc(1)).unwrap();
+    put_and_get(&store);
+    assert!(path.is_file());
+    assert!(dir.join("database.mdb-lock").is_file());
+
+    drop(store);
+    test_root.delete();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_mode_applied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let path = test_root.fresh_test_dir("file_mode").as_ref().to_path_buf();
+
+    let store = lmdb(path.clone()).with_file_mode(0o600).open(desc(1)).unwrap();
+    let mode = std::fs::metadata(path.join("data.mdb")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn invalid_options_rejected() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let check = |name: &str, configure: fn(Lmdb) -> Lmdb, msg: &str| {
+        let path = test_root.fresh_test_dir(name).as_ref().to_path_buf();
+        let result = configure(lmdb(path)).open(desc(1));
+        assert_eq!(
+            result.err(),
+            Some(Fatal::InvalidOptions(msg.into()).into()),
+            "{name}"
+        );
+    };
+
+    check(
+        "map_async",
+        |lmdb| lmdb.with_map_async(),
+        "asynchronous map flushes require a writable memory map",
+    );
+    check(
+        "max_readers",
+        |lmdb| lmdb.with_max_readers(0),
+        "the maximum number of readers must be positive",
+    );
+    check(
+        "file_mode_bits",
+        |lmdb| lmdb.with_file_mode(0o4644),
+        "file mode may only contain permission bits",
+    );
+    check(
+        "file_mode_owner",
+        |lmdb| lmdb.with_file_mode(0o444),
+        "file mode has to allow the owner to read and write",
+    );
+    check(
+        "no_lock_readers",
+        |lmdb| lmdb.with_no_lock().with_max_readers(2),
+        "more than one reader requires locking",
+    );
+    check(
+        "no_lock_no_tls",
+        |lmdb| lmdb.with_no_lock().with_no_tls(),
+        "reader slots tied to transactions require locking",
+    );
+    check(
+        "no_lock_reader_check",
+        |lmdb| lmdb.with_no_lock().with_reader_check_interval(Duration::from_secs(1)),
+        "stale reader checks require locking",
+    );
+
+    test_root.delete();
+}
+
+#[test]
+fn no_lock_with_single_reader() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let path = test_root.fresh_test_dir("no_lock").as_ref().to_path_buf();
+
+    let store = lmdb(path).with_no_lock().with_max_readers(1).open(desc(1)).unwrap();
+    put_and_get(&store);
+
+    drop(store);
+    test_root.delete();
+}
@@ -0,0 +1,106 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the maintenance of the reader lock table
+
+use std::{path::PathBuf, time::Duration};
+
+use storage_backend_test_suite::prelude::*;
+use storage_lmdb::Lmdb;
+
+/// Environment variable telling the child process where the database is
+const CHILD_DB_PATH_VAR: &str = "STORAGE_LMDB_STALE_READER_DB";
+
+fn lmdb(path: PathBuf) -> Lmdb {
+    Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+}
+
+/// Run in a child process, leaving a stale reader behind. Does nothing when run as a test.
+#[test]
+#[ignore]
+fn stale_reader_child() {
+    if let Some(path) = std::env::var_os(CHILD_DB_PATH_VAR) {
+        let store = lmdb(path.into()).open(desc(1)).unwrap();
+        let dbtx = store.transaction_ro().unwrap();
+        std::mem::forget(dbtx);
+        std::process::exit(0);
+    }
+}
+
+/// Spawn a process that gets killed while reading the database, returning its PID
+fn leave_stale_reader(path: &std::path::Path) -> u32 {
+    let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["stale_reader_child", "--exact", "--ignored", "--test-threads=1"])
+        .env(CHILD_DB_PATH_VAR, path)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    child.id()
+}
+
+#[test]
+fn stale_readers_cleared() {
+    let test_root = test_utils::test_root!("readers-tests").unwrap();
+    let path = test_root.fresh_test_dir("manual").as_ref().to_path_buf();
+
+    let store = lmdb(path.clone()).open(desc(1)).unwrap();
+    assert_eq!(store.check_readers(), Ok(0));
+
+    let pid = leave_stale_reader(&path);
+    let reader_list = store.reader_list().unwrap();
+    assert!(
+        reader_list
+            .lines()
+            .skip(1)
+            .any(|line| line.split_whitespace().next() == Some(&pid.to_string())),
+        "{reader_list}"
+    );
+
+    assert_eq!(store.check_readers(), Ok(1));
+    assert_eq!(store.check_readers(), Ok(0));
+    assert_eq!(store.stale_readers_cleared(), 1);
+    assert!(!store.reader_list().unwrap().contains(&pid.to_string()));
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn stale_readers_cleared_periodically() {
+    let test_root = test_utils::test_root!("readers-tests").unwrap();
+    let path = test_root.fresh_test_dir("periodic").as_ref().to_path_buf();
+
+    let store = lmdb(path.clone())
+        .with_reader_check_interval(Duration::ZERO)
+        .open(desc(1))
+        .unwrap();
+    leave_stale_reader(&path);
+    assert_eq!(store.stale_readers_cleared(), 0);
+
+    // Starting a transaction runs the check
+    let dbtx = store.transaction_ro().unwrap();
+    assert_eq!(dbtx.get(MAPID.0, b"key"), Ok(None));
+    assert_eq!(store.stale_readers_cleared(), 1);
+
+    drop(dbtx);
+    drop(store);
+    test_root.delete();
+}
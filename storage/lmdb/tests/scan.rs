@@ -0,0 +1,86 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timing of full map scans, ignored by default. Run it in release mode with
+//!
+//! ```text
+//! cargo test --release -p storage-lmdb --test scan -- --ignored --nocapture
+//! ```
+//!
+//! Scanning with entries borrowed from the database is compared with copying each entry out,
+//! which is what the iterators did before they started yielding borrowed entries.
+
+use std::{hint::black_box, path::PathBuf, time::Duration};
+
+use storage_backend_test_suite::prelude::*;
+use storage_lmdb::Lmdb;
+
+const NUM_ENTRIES: u32 = 50_000;
+const VALUE_SIZE: usize = 4096;
+const RUNS: usize = 20;
+
+fn lmdb(path: PathBuf) -> Lmdb {
+    Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+}
+
+/// Shortest time it takes to go through all the entries, processing each with given function
+fn best_scan_time(store: &impl BackendImpl, mut process: impl FnMut(Entry<'_>)) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let dbtx = store.transaction_ro().unwrap();
+            let start = std::time::Instant::now();
+            for entry in dbtx.prefix_iter(MAPID.0, Data::new()).unwrap() {
+                process(entry.unwrap());
+            }
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one run")
+}
+
+#[test]
+#[ignore]
+fn full_scan_timing() {
+    let test_root = test_utils::test_root!("scan-tests").unwrap();
+    let path = test_root.fresh_test_dir("scan").as_ref().to_path_buf();
+    let store = lmdb(path).open(desc(1)).unwrap();
+
+    // Values this large go to overflow pages, so each one takes up two pages
+    let size = NUM_ENTRIES as usize * 2 * (VALUE_SIZE + 4096);
+    let mut dbtx = store.transaction_rw(Some(size)).unwrap();
+    for i in 0..NUM_ENTRIES {
+        dbtx.put(MAPID.0, i.to_be_bytes().to_vec(), vec![i as u8; VALUE_SIZE]).unwrap();
+    }
+    dbtx.commit().unwrap();
+
+    let borrowed = best_scan_time(&store, |(key, val)| {
+        black_box((key.len(), val.len()));
+    });
+    let copied = best_scan_time(&store, |(key, val)| {
+        black_box((key.into_owned(), val.into_owned()));
+    });
+    println!(
+        "Full scan of {NUM_ENTRIES} entries with {VALUE_SIZE} byte values, best of {RUNS} runs: \
+         {borrowed:?} borrowed, {copied:?} copied"
+    );
+
+    drop(store);
+    test_root.delete();
+}
@@ -0,0 +1,70 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the environment and database map statistics
+
+use storage_backend_test_suite::prelude::*;
+use storage_lmdb::{stats::MapStats, Lmdb};
+
+#[test]
+fn stats_reported() {
+    let test_root = test_utils::test_root!("stats-tests").unwrap();
+    let path = test_root.fresh_test_dir("stats").as_ref().to_path_buf();
+
+    let desc = storage_core::types::construct::db_desc(
+        [DbMapDesc::new("Full"), DbMapDesc::new("Empty")].into_iter(),
+    );
+    let store = Lmdb::new(
+        path,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .with_max_readers(10)
+    .open(desc)
+    .unwrap();
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for i in 0u32..100 {
+        dbtx.put(DbMapId::new(0), i.to_be_bytes().to_vec(), vec![0; 100]).unwrap();
+    }
+    dbtx.commit().unwrap();
+
+    let stats = store.stats().unwrap();
+    assert_eq!(stats.max_readers, 10);
+    assert!(stats.last_transaction_id >= 1);
+    assert!(stats.occupied_size() <= stats.map_size);
+    let fill_permille = stats.occupied_size() * 1000 / stats.map_size;
+    assert_eq!(stats.fill_permille(), fill_permille as u64);
+    assert!(stats.fill_permille() <= 1000);
+
+    assert_eq!(stats.maps.len(), 2);
+    assert_eq!(stats.maps["Full"].entries, 100);
+    assert!(stats.maps["Full"].depth >= 1);
+    assert!(stats.maps["Full"].leaf_pages >= 1);
+    assert_eq!(
+        stats.maps["Empty"],
+        MapStats {
+            depth: 0,
+            branch_pages: 0,
+            leaf_pages: 0,
+            overflow_pages: 0,
+            entries: 0,
+        }
+    );
+
+    drop(store);
+    test_root.delete();
+}
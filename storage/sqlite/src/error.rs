@@ -13,7 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use rusqlite::Error as SqlError;
+use rusqlite::{ffi, Error as SqlError, ErrorCode};
 use std::io::{Error as IoError, ErrorKind};
 
 use storage_core::error::{Fatal, Recoverable};
@@ -29,13 +29,51 @@ pub fn process_io_error(err: IoError) -> storage_core::Error {
     }
 }
 
+/// Get the I/O error kind corresponding to an extended `SQLITE_IOERR_*` result code
+fn io_error_kind(extended_code: std::os::raw::c_int) -> ErrorKind {
+    match extended_code {
+        ffi::SQLITE_IOERR_NOMEM => ErrorKind::OutOfMemory,
+        ffi::SQLITE_IOERR_SHORT_READ => ErrorKind::UnexpectedEof,
+        ffi::SQLITE_IOERR_ACCESS => ErrorKind::PermissionDenied,
+        ffi::SQLITE_IOERR_DELETE_NOENT => ErrorKind::NotFound,
+        _ => ErrorKind::Other,
+    }
+}
+
 /// Map Sqlite error into a storage error
 pub fn process_sqlite_error(err: rusqlite::Error) -> storage_core::Error {
-    // TODO Improve error conversions
-    match err {
-        SqlError::SqliteFailure(err, err_str) => {
-            Fatal::InternalError(err_str.unwrap_or_else(|| err.to_string())).into()
+    let (err, msg) = match err {
+        SqlError::SqliteFailure(err, msg) => (err, msg.unwrap_or_else(|| err.to_string())),
+        // Failures not coming from the database engine itself signify an implementation flaw
+        err => return Fatal::InternalError(err.to_string()).into(),
+    };
+
+    match err.code {
+        // The database is in use by another connection or process
+        ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => {
+            Recoverable::TemporarilyUnavailable.into()
         }
-        _ => Fatal::InternalError(err.to_string()).into(),
+
+        // Initialization issues
+        ErrorCode::NotADatabase => Recoverable::DbInit.into(),
+
+        // Database corruption issues
+        ErrorCode::DatabaseCorrupt => Fatal::DatabaseCorrupted.into(),
+
+        // Running out of disk space
+        ErrorCode::DiskFull => Fatal::OutOfSpace.into(),
+
+        // I/O issues, classified as recoverable vs. fatal the same way as system I/O errors
+        ErrorCode::SystemIoFailure => {
+            process_io_error(IoError::new(io_error_kind(err.extended_code), msg))
+        }
+        ErrorCode::CannotOpen => process_io_error(IoError::new(ErrorKind::NotFound, msg)),
+        ErrorCode::PermissionDenied | ErrorCode::ReadOnly => {
+            process_io_error(IoError::new(ErrorKind::PermissionDenied, msg))
+        }
+        ErrorCode::OutOfMemory => process_io_error(IoError::new(ErrorKind::OutOfMemory, msg)),
+
+        // Everything else signifies an implementation flaw
+        _ => Fatal::InternalError(msg).into(),
     }
 }
@@ -0,0 +1,139 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::ErrorKind;
+
+use rusqlite::ffi;
+use storage_core::error::{Fatal, Recoverable};
+
+use super::*;
+
+fn memory_db(name: &str) -> Connection {
+    let flags = OpenFlags::from_iter([
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        OpenFlags::SQLITE_OPEN_CREATE,
+        OpenFlags::SQLITE_OPEN_URI,
+    ]);
+    let uri = format!("file:{name}?mode=memory&cache=shared");
+    let connection = Connection::open_with_flags(uri, flags).unwrap();
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS t(key BLOB PRIMARY KEY, value BLOB)",
+            (),
+        )
+        .unwrap();
+    connection
+}
+
+fn sqlite_failure(extended_code: std::os::raw::c_int) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(extended_code), None)
+}
+
+#[test]
+fn locked_is_temporarily_unavailable() {
+    let writer = memory_db("locked");
+    let reader = memory_db("locked");
+
+    // Connections sharing a cache lock tables against each other
+    writer.execute_batch("BEGIN; INSERT INTO t VALUES (x'00', x'00')").unwrap();
+    let err = reader.query_row("SELECT COUNT(*) FROM t", (), |row| row.get::<_, i64>(0));
+    let err = err.unwrap_err();
+
+    assert_eq!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseLocked)
+    );
+    assert_eq!(
+        process_sqlite_error(err),
+        Recoverable::TemporarilyUnavailable.into()
+    );
+}
+
+#[test]
+fn full_is_out_of_space() {
+    let connection = memory_db("full");
+
+    // Cap the database size so it cannot grow any further
+    connection.pragma_update(None, "max_page_count", 2).unwrap();
+    let mut stmt = connection.prepare("INSERT INTO t VALUES (?, zeroblob(100000))").unwrap();
+    let err = (0u8..).find_map(|i| stmt.execute([vec![i]]).err()).unwrap();
+
+    assert_eq!(err.sqlite_error_code(), Some(rusqlite::ErrorCode::DiskFull));
+    assert_eq!(process_sqlite_error(err), Fatal::OutOfSpace.into());
+}
+
+#[test]
+fn corrupt_is_database_corrupted() {
+    let connection = memory_db("corrupt");
+
+    // Point the table at a page that does not exist
+    connection
+        .execute_batch(
+            "PRAGMA writable_schema = ON;
+             UPDATE sqlite_master SET rootpage = 1000 WHERE name = 't';
+             PRAGMA writable_schema = RESET;",
+        )
+        .unwrap();
+    let err = connection.query_row("SELECT COUNT(*) FROM t", (), |row| row.get::<_, i64>(0));
+    let err = err.unwrap_err();
+
+    assert_eq!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseCorrupt)
+    );
+    assert_eq!(process_sqlite_error(err), Fatal::DatabaseCorrupted.into());
+}
+
+#[test]
+fn io_errors_classified() {
+    let check = |extended_code, expected_kind: ErrorKind, recoverable: bool| {
+        let err = process_sqlite_error(sqlite_failure(extended_code));
+        let (kind, is_recoverable) = match err {
+            storage_core::Error::Recoverable(Recoverable::Io(kind, _)) => (kind, true),
+            storage_core::Error::Fatal(Fatal::Io(kind, _)) => (kind, false),
+            err => panic!("Unexpected error {err:?}"),
+        };
+        assert_eq!(kind, expected_kind, "code {extended_code}");
+        assert_eq!(is_recoverable, recoverable, "code {extended_code}");
+    };
+
+    check(ffi::SQLITE_IOERR_WRITE, ErrorKind::Other, false);
+    check(ffi::SQLITE_IOERR_FSYNC, ErrorKind::Other, false);
+    check(
+        ffi::SQLITE_IOERR_SHORT_READ,
+        ErrorKind::UnexpectedEof,
+        false,
+    );
+    check(ffi::SQLITE_IOERR_NOMEM, ErrorKind::OutOfMemory, false);
+    check(ffi::SQLITE_IOERR_ACCESS, ErrorKind::PermissionDenied, true);
+    check(ffi::SQLITE_IOERR_DELETE_NOENT, ErrorKind::NotFound, true);
+    check(ffi::SQLITE_CANTOPEN, ErrorKind::NotFound, true);
+    check(ffi::SQLITE_READONLY, ErrorKind::PermissionDenied, true);
+}
+
+#[test]
+fn other_errors_internal() {
+    let err = process_sqlite_error(sqlite_failure(ffi::SQLITE_CONSTRAINT_PRIMARYKEY));
+    assert!(matches!(
+        err,
+        storage_core::Error::Fatal(Fatal::InternalError(_))
+    ));
+
+    let err = process_sqlite_error(rusqlite::Error::QueryReturnedNoRows);
+    assert!(matches!(
+        err,
+        storage_core::Error::Fatal(Fatal::InternalError(_))
+    ));
+}
@@ -16,60 +16,207 @@
 extern crate core;
 
 mod error;
+mod options;
+mod pool;
 mod queries;
 
-use rusqlite::{Connection, OpenFlags, OptionalExtension};
+pub use options::{
+    AccessMode, AutoVacuum, CacheSize, EffectiveOptions, JournalMode, Options, Synchronous,
+    TempStore, DEFAULT_WAL_BUSY_TIMEOUT,
+};
+
+use rusqlite::{CachedStatement, Connection, OpenFlags, OptionalExtension};
 use std::borrow::Cow;
 use std::cmp::max;
+use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
-use std::sync::{Mutex, MutexGuard};
+use std::ptr::NonNull;
+use std::sync::Mutex;
 
 use crate::queries::SqliteQueries;
 use error::process_sqlite_error;
-use storage_core::{backend, Data, DbDesc, DbMapId};
+use pool::{ReaderPool, TxConnection};
+use storage_core::{backend, util, Data, DbDesc, DbMapId};
 use utils::shallow_clone::ShallowClone;
 use utils::sync::Arc;
 
-/// Sqlite iterator over entries with given key prefix
-pub struct PrefixIter {
-    /// Underlying iterator
-    iter: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+/// Sqlite iterator stepping through the rows of a query result as they are requested.
+///
+/// The iterator owns the statement the rows come from, so only the current row is held in memory.
+pub struct RowIter<'i, T> {
+    /// Rows of the query result, borrowing the statement. Present until the iteration ends.
+    rows: Option<rusqlite::Rows<'i>>,
+
+    /// The statement being executed, allocated by `Box::into_raw` and released on drop
+    stmt: NonNull<CachedStatement<'i>>,
 
-    /// Prefix to iterate over
-    prefix: Data,
+    /// Conversion of a row into an iterator item
+    row_fn: fn(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
 }
 
-impl PrefixIter {
-    fn new(iter: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>, prefix: Data) -> Self {
-        PrefixIter { iter, prefix }
+impl<'i, T> RowIter<'i, T> {
+    fn new(
+        stmt: CachedStatement<'i>,
+        params: impl rusqlite::Params,
+        row_fn: fn(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    ) -> storage_core::Result<Self> {
+        let stmt = NonNull::from(Box::leak(Box::new(stmt)));
+        let mut iter = RowIter {
+            rows: None,
+            stmt,
+            row_fn,
+        };
+        // SAFETY: The statement lives on the heap at a fixed address and is only released in
+        // `drop` after the rows, which are the only thing borrowing it, have been dropped.
+        let stmt = unsafe { &mut *iter.stmt.as_ptr() };
+        iter.rows = Some(stmt.query(params).map_err(process_sqlite_error)?);
+        Ok(iter)
     }
 }
 
-impl Iterator for PrefixIter {
-    type Item = (Data, Data);
+impl<T> Iterator for RowIter<'_, T> {
+    type Item = storage_core::Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let kv = self.iter.next()?;
-        utils::ensure!(kv.0.starts_with(&self.prefix));
-        Some(kv)
+        let item = match self.rows.as_mut()?.next() {
+            Ok(Some(row)) => Some((self.row_fn)(row)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        };
+        // Stepping the statement any further after it is done or has failed makes no sense
+        if !matches!(item, Some(Ok(_))) {
+            self.rows = None;
+        }
+        item.map(|item| item.map_err(process_sqlite_error))
+    }
+}
+
+impl<T> Drop for RowIter<'_, T> {
+    fn drop(&mut self) {
+        self.rows = None;
+        // SAFETY: The pointer comes from `Box::leak` in `new` and the rows borrowing the
+        // statement are gone by now.
+        drop(unsafe { Box::from_raw(self.stmt.as_ptr()) });
+    }
+}
+
+/// Entries with keys in a range, fetched from the database
+type RangeEntries<'i> = RowIter<'i, (Data, Data)>;
+
+/// Iterator over entries fetched from the database, handed out as owned data
+type OwnedEntries<'i, I> = std::iter::Map<
+    I,
+    fn(storage_core::Result<(Data, Data)>) -> storage_core::Result<backend::Entry<'i>>,
+>;
+
+/// Iterator over keys fetched from the database, handed out as owned data
+type OwnedKeys<'i> = std::iter::Map<
+    RowIter<'i, Data>,
+    fn(storage_core::Result<Data>) -> storage_core::Result<Cow<'i, [u8]>>,
+>;
+
+fn owned_entry<'i>(
+    kv: storage_core::Result<(Data, Data)>,
+) -> storage_core::Result<backend::Entry<'i>> {
+    kv.map(|(k, v)| (k.into(), v.into()))
+}
+
+fn owned_key<'i>(key: storage_core::Result<Data>) -> storage_core::Result<Cow<'i, [u8]>> {
+    key.map(Cow::Owned)
+}
+
+/// Sqlite cursor over entries of a table.
+///
+/// The position is tracked by the key of the current entry, each move is a separate query.
+pub struct DbCursor<'i, 'm> {
+    /// The transaction the cursor belongs to
+    tx: &'i DbTx<'m>,
+
+    /// The table to move over
+    map_id: DbMapId,
+
+    /// The entry the cursor is positioned at
+    current: Option<(Data, Data)>,
+}
+
+impl<'i, 'm> DbCursor<'i, 'm> {
+    fn new(tx: &'i DbTx<'m>, map_id: DbMapId) -> Self {
+        let current = None;
+        DbCursor {
+            tx,
+            map_id,
+            current,
+        }
+    }
+
+    /// Move to the first entry in given range and direction
+    fn move_to(
+        &mut self,
+        start: Bound<Data>,
+        end: Bound<Data>,
+        descending: bool,
+    ) -> storage_core::Result<Option<(Data, Data)>> {
+        self.current = None;
+        let mut entries = self.tx.query_range(self.map_id, start, end, descending, Some(1))?;
+        self.current = entries.next().transpose()?;
+        Ok(self.current.clone())
     }
 }
 
+impl backend::Cursor for DbCursor<'_, '_> {
+    fn seek(&mut self, key: &[u8]) -> storage_core::Result<Option<(Data, Data)>> {
+        self.move_to(Bound::Included(key.to_vec()), Bound::Unbounded, false)
+    }
+
+    fn first(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        self.move_to(Bound::Unbounded, Bound::Unbounded, false)
+    }
+
+    fn last(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        self.move_to(Bound::Unbounded, Bound::Unbounded, true)
+    }
+
+    fn next(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        match self.current.take() {
+            Some((key, _)) => self.move_to(Bound::Excluded(key), Bound::Unbounded, false),
+            None => self.first(),
+        }
+    }
+
+    fn prev(&mut self) -> storage_core::Result<Option<(Data, Data)>> {
+        match self.current.take() {
+            Some((key, _)) => self.move_to(Bound::Unbounded, Bound::Excluded(key), true),
+            None => self.last(),
+        }
+    }
+
+    fn current(&self) -> storage_core::Result<Option<(Data, Data)>> {
+        Ok(self.current.clone())
+    }
+}
+
+/// Name of the savepoint at given nesting depth
+fn savepoint_name(depth: usize) -> String {
+    format!("savepoint_{depth}")
+}
+
 pub struct DbTx<'m> {
-    connection: MutexGuard<'m, Connection>,
+    connection: TxConnection<'m>,
     queries: &'m SqliteQueries,
+    /// Number of active savepoints
+    savepoints: usize,
 }
 
 impl<'m> DbTx<'m> {
-    fn start_transaction(sqlite: &'m SqliteImpl) -> storage_core::Result<Self> {
-        let connection = sqlite
-            .0
-            .connection
-            .lock()
-            .map_err(|e| storage_core::error::Fatal::InternalError(e.to_string()))?;
+    fn start_transaction(
+        connection: TxConnection<'m>,
+        queries: &'m SqliteQueries,
+    ) -> storage_core::Result<Self> {
         let tx = DbTx {
             connection,
-            queries: &sqlite.0.queries,
+            queries,
+            savepoints: 0,
         };
         tx.connection.execute("BEGIN TRANSACTION", ()).map_err(process_sqlite_error)?;
         Ok(tx)
@@ -96,8 +243,12 @@ impl Drop for DbTx<'_> {
     }
 }
 
-impl backend::ReadOps for DbTx<'_> {
-    type PrefixIter<'i> = PrefixIter where Self: 'i;
+impl<'m> backend::ReadOps for DbTx<'m> {
+    type PrefixIter<'i> = OwnedEntries<'i, RangeEntries<'i>> where Self: 'i;
+    type PrefixKeyIter<'i> = OwnedKeys<'i> where Self: 'i;
+    type RangeIter<'i> = OwnedEntries<'i, RangeEntries<'i>> where Self: 'i;
+    type RangeIterRev<'i> = OwnedEntries<'i, RangeEntries<'i>> where Self: 'i;
+    type Cursor<'i> = DbCursor<'i, 'm> where Self: 'i;
 
     fn get(&self, map_id: DbMapId, key: &[u8]) -> storage_core::Result<Option<Cow<[u8]>>> {
         let mut stmt = self
@@ -119,30 +270,85 @@ impl backend::ReadOps for DbTx<'_> {
         map_id: DbMapId,
         prefix: Data,
     ) -> storage_core::Result<Self::PrefixIter<'_>> {
-        // TODO check if prefix.is_empty()
-        // TODO Perform the filtering in the SQL query itself
-        let mut stmt = self
-            .connection
-            .prepare_cached(self.queries[map_id].prefix_iter_query.as_str())
-            .map_err(process_sqlite_error)?;
+        let (start, end) = util::prefix_range(prefix);
+        Ok(self.query_range(map_id, start, end, false, None)?.map(owned_entry as _))
+    }
 
-        let mut rows = stmt.query(()).map_err(process_sqlite_error)?;
+    fn prefix_iter_keys(
+        &self,
+        map_id: DbMapId,
+        prefix: Data,
+    ) -> storage_core::Result<Self::PrefixKeyIter<'_>> {
+        let (start, end) = util::prefix_range(prefix);
+        let query = queries::range_iter_keys_query(
+            &self.queries[map_id].table_name,
+            start.as_ref(),
+            end.as_ref(),
+        );
+        let stmt = self.connection.prepare_cached(&query).map_err(process_sqlite_error)?;
+
+        let params = rusqlite::params_from_iter(range_params(start, end));
+        let iter = RowIter::new(stmt, params, |row| row.get(0))?;
+
+        Ok(iter.map(owned_key as _))
+    }
 
-        // TODO Move the statement/rows in to the PrefixIter
-        let mut kv = Vec::new();
-        while let Some(row) = rows.next().map_err(process_sqlite_error)? {
-            let key = row.get::<usize, Vec<u8>>(0).map_err(process_sqlite_error)?;
-            if key.starts_with(&prefix) {
-                let value = row.get::<usize, Vec<u8>>(1).map_err(process_sqlite_error)?;
-                kv.push((key, value));
-            }
-        }
-        let kv_iter = kv.into_iter();
+    fn range_iter(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIter<'_>> {
+        Ok(self.query_range(map_id, start, end, false, None)?.map(owned_entry as _))
+    }
+
+    fn range_iter_rev(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<Self::RangeIterRev<'_>> {
+        Ok(self.query_range(map_id, start, end, true, None)?.map(owned_entry as _))
+    }
 
-        Ok(PrefixIter::new(kv_iter, prefix))
+    fn cursor(&self, map_id: DbMapId) -> storage_core::Result<Self::Cursor<'_>> {
+        Ok(DbCursor::new(self, map_id))
     }
 }
 
+impl DbTx<'_> {
+    /// Query the entries with keys in given range, in ascending or descending key order, taking
+    /// at most `limit` entries if given. The entries are fetched as the iteration proceeds.
+    fn query_range(
+        &self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+        descending: bool,
+        limit: Option<usize>,
+    ) -> storage_core::Result<RangeEntries<'_>> {
+        let query = queries::range_iter_query(
+            &self.queries[map_id].table_name,
+            start.as_ref(),
+            end.as_ref(),
+            descending,
+            limit,
+        );
+        let stmt = self.connection.prepare_cached(&query).map_err(process_sqlite_error)?;
+
+        let params = rusqlite::params_from_iter(range_params(start, end));
+        RowIter::new(stmt, params, |row| Ok((row.get(0)?, row.get(1)?)))
+    }
+}
+
+/// Query parameters for the bounded ends of a key range, start bound first
+fn range_params(start: Bound<Data>, end: Bound<Data>) -> impl Iterator<Item = Data> {
+    [start, end].into_iter().filter_map(|bound| match bound {
+        Bound::Included(key) | Bound::Excluded(key) => Some(key),
+        Bound::Unbounded => None,
+    })
+}
+
 impl backend::WriteOps for DbTx<'_> {
     fn put(&mut self, map_id: DbMapId, key: Data, val: Data) -> storage_core::Result<()> {
         let mut stmt = self
@@ -167,6 +373,72 @@ impl backend::WriteOps for DbTx<'_> {
 
         Ok(())
     }
+
+    fn del_range(
+        &mut self,
+        map_id: DbMapId,
+        start: Bound<Data>,
+        end: Bound<Data>,
+    ) -> storage_core::Result<()> {
+        let query = queries::range_delete_query(
+            &self.queries[map_id].table_name,
+            start.as_ref(),
+            end.as_ref(),
+        );
+        let mut stmt = self.connection.prepare_cached(&query).map_err(process_sqlite_error)?;
+
+        let params = range_params(start, end);
+        let _res =
+            stmt.execute(rusqlite::params_from_iter(params)).map_err(process_sqlite_error)?;
+
+        Ok(())
+    }
+
+    fn put_if_absent(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        val: Data,
+    ) -> storage_core::Result<bool> {
+        let mut stmt = self
+            .connection
+            .prepare_cached(self.queries[map_id].insert_if_absent_query.as_str())
+            .map_err(process_sqlite_error)?;
+
+        let params = (key, val);
+        let changed = stmt.execute(params).map_err(process_sqlite_error)?;
+
+        Ok(changed > 0)
+    }
+
+    fn compare_and_swap(
+        &mut self,
+        map_id: DbMapId,
+        key: Data,
+        expected: Option<&[u8]>,
+        new: Option<Data>,
+    ) -> storage_core::Result<bool> {
+        // The condition is evaluated as a part of the modifying statement itself
+        let (query, params) = match (expected, new) {
+            (None, Some(new)) => return self.put_if_absent(map_id, key, new),
+            (None, None) => {
+                return backend::ReadOps::get(self, map_id, &key).map(|val| val.is_none())
+            }
+            (Some(expected), Some(new)) => (
+                &self.queries[map_id].update_if_equal_query,
+                rusqlite::params_from_iter(vec![new, key, expected.to_vec()]),
+            ),
+            (Some(expected), None) => (
+                &self.queries[map_id].delete_if_equal_query,
+                rusqlite::params_from_iter(vec![key, expected.to_vec()]),
+            ),
+        };
+
+        let mut stmt = self.connection.prepare_cached(query).map_err(process_sqlite_error)?;
+        let changed = stmt.execute(params).map_err(process_sqlite_error)?;
+
+        Ok(changed > 0)
+    }
 }
 
 impl backend::TxRo for DbTx<'_> {}
@@ -175,24 +447,82 @@ impl backend::TxRw for DbTx<'_> {
     fn commit(self) -> storage_core::Result<()> {
         self.commit_transaction()
     }
+
+    fn savepoint(&mut self) -> storage_core::Result<()> {
+        let name = savepoint_name(self.savepoints + 1);
+        self.connection
+            .execute(&format!("SAVEPOINT {name}"), ())
+            .map_err(process_sqlite_error)?;
+        self.savepoints += 1;
+        Ok(())
+    }
+
+    fn rollback_to(&mut self) -> storage_core::Result<()> {
+        utils::ensure!(self.savepoints > 0, storage_core::error::Fatal::NoSavepoint);
+        let name = savepoint_name(self.savepoints);
+        // Rolling back to a savepoint keeps it active, so it has to be released afterwards
+        self.connection
+            .execute_batch(&format!("ROLLBACK TO {name}; RELEASE {name}"))
+            .map_err(process_sqlite_error)?;
+        self.savepoints -= 1;
+        Ok(())
+    }
+
+    fn release(&mut self) -> storage_core::Result<()> {
+        utils::ensure!(self.savepoints > 0, storage_core::error::Fatal::NoSavepoint);
+        let name = savepoint_name(self.savepoints);
+        self.connection
+            .execute(&format!("RELEASE {name}"), ())
+            .map_err(process_sqlite_error)?;
+        self.savepoints -= 1;
+        Ok(())
+    }
 }
 
 /// Struct that holds the details for an Sqlite connection
 pub struct SqliteConnection {
-    /// Handle to an Sqlite database connection
+    /// Handle to an Sqlite database connection. All read-write transactions go through it, as do
+    /// the read-only ones unless there is a pool of readers.
     connection: Mutex<Connection>,
 
+    /// Connections for read-only transactions, present in WAL mode
+    readers: Option<ReaderPool>,
+
     /// List of sql queries
     queries: SqliteQueries,
+
+    /// Settings in effect, as read back after opening the database
+    options: EffectiveOptions,
 }
 
 #[derive(Clone)]
 pub struct SqliteImpl(Arc<SqliteConnection>);
 
 impl SqliteImpl {
-    /// Start a transaction using the low-level method provided
+    /// Settings in effect for the database, as reported by SQLite once it has been opened
+    pub fn options(&self) -> &EffectiveOptions {
+        &self.0.options
+    }
+
+    /// Start a transaction on the main connection
     fn start_transaction(&self) -> storage_core::Result<DbTx<'_>> {
-        DbTx::start_transaction(self)
+        let connection = self
+            .0
+            .connection
+            .lock()
+            .map_err(|e| storage_core::error::Fatal::InternalError(e.to_string()))?;
+        DbTx::start_transaction(TxConnection::Locked(connection), &self.0.queries)
+    }
+
+    /// Start a read-only transaction, on a pooled connection if available
+    fn start_transaction_ro(&self) -> storage_core::Result<DbTx<'_>> {
+        match &self.0.readers {
+            Some(readers) => {
+                let connection = TxConnection::Pooled(readers.acquire()?);
+                DbTx::start_transaction(connection, &self.0.queries)
+            }
+            None => self.start_transaction(),
+        }
     }
 }
 
@@ -208,7 +538,7 @@ impl backend::BackendImpl for SqliteImpl {
     type TxRw<'a> = DbTx<'a>;
 
     fn transaction_ro(&self) -> storage_core::Result<Self::TxRo<'_>> {
-        self.start_transaction()
+        self.start_transaction_ro()
     }
 
     fn transaction_rw(&self, _size: Option<usize>) -> storage_core::Result<Self::TxRw<'_>> {
@@ -216,22 +546,6 @@ impl backend::BackendImpl for SqliteImpl {
     }
 }
 
-#[derive(Eq, PartialEq, Clone, Debug)]
-pub struct Options {
-    /// If enabled, sets synchronous pragma to OFF, see <https://www.sqlite.org/pragma.html#pragma_synchronous>.
-    /// It should normally only be used in unit tests.
-    pub disable_fsync: bool,
-}
-
-#[allow(clippy::derivable_impls)]
-impl Default for Options {
-    fn default() -> Self {
-        Self {
-            disable_fsync: false,
-        }
-    }
-}
-
 #[derive(Eq, PartialEq, Clone, Debug)]
 enum SqliteStorageMode {
     InMemory,
@@ -267,65 +581,209 @@ impl Sqlite {
         }
     }
 
-    fn open_db(self, desc: DbDesc) -> rusqlite::Result<Connection> {
+    fn open_db(&self, desc: &DbDesc) -> rusqlite::Result<Connection> {
         let flags = OpenFlags::from_iter([
             OpenFlags::SQLITE_OPEN_FULL_MUTEX,
             OpenFlags::SQLITE_OPEN_READ_WRITE,
             OpenFlags::SQLITE_OPEN_CREATE,
         ]);
 
-        let connection = match self.backend {
+        let connection = match &self.backend {
             SqliteStorageMode::InMemory => Connection::open_in_memory_with_flags(flags)?,
             SqliteStorageMode::File(path) => Connection::open_with_flags(path, flags)?,
         };
 
-        let Options { disable_fsync } = self.options;
+        let options = &self.options;
+
+        // These only take effect while the database is empty, so they have to come first
+        if let Some(page_size) = options.page_size {
+            connection.pragma_update(None, "page_size", page_size)?;
+        }
+        if let Some(auto_vacuum) = options.auto_vacuum {
+            connection.pragma_update(None, "auto_vacuum", auto_vacuum)?;
+        }
 
-        // Set the locking mode to exclusive
-        connection.pragma_update(None, "locking_mode", "exclusive")?;
+        let journal_mode = match options.access_mode {
+            AccessMode::Exclusive => {
+                // Set the locking mode to exclusive
+                connection.pragma_update(None, "locking_mode", "exclusive")?;
+                options.journal_mode
+            }
+            // The journal mode is persistent, the readers pick it up from the database file
+            AccessMode::Wal { readers: _ } => Some(JournalMode::Wal),
+        };
+        if let Some(journal_mode) = journal_mode {
+            connection.pragma_update(None, "journal_mode", journal_mode)?;
+        }
 
-        if disable_fsync {
-            connection.pragma_update(None, "synchronous", "OFF")?;
-        } else {
+        if let Some(synchronous) = options.synchronous() {
+            connection.pragma_update(None, "synchronous", synchronous)?;
+        }
+        if !options.disable_fsync {
             // Enable fullfsync (only affects macOS)
             connection.pragma_update(None, "fullfsync", "true")?;
         }
 
-        // Begin a transaction to acquire the exclusive lock
-        connection.execute("BEGIN EXCLUSIVE TRANSACTION", ())?;
-        connection.execute("COMMIT", ())?;
+        self.set_connection_options(&connection, desc)?;
+
+        if options.access_mode == AccessMode::Exclusive {
+            // Begin a transaction to acquire the exclusive lock
+            connection.execute("BEGIN EXCLUSIVE TRANSACTION", ())?;
+            connection.execute("COMMIT", ())?;
+        }
+
+        Ok(connection)
+    }
+
+    /// Create the tables for the key/value databases that are missing and check that the
+    /// recorded properties of those already present match their descriptions
+    /// Read all entries of the map with given name if its table exists, without creating it
+    fn read_existing_map(
+        connection: &Connection,
+        name: &str,
+    ) -> storage_core::Result<BTreeMap<Data, Data>> {
+        let exists = connection
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name=?",
+                [name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(process_sqlite_error)?
+            .is_some();
+        if !exists {
+            return Ok(BTreeMap::new());
+        }
+
+        let table_name = queries::quote_identifier(name);
+        let mut stmt = connection
+            .prepare(&format!("SELECT key, value FROM {table_name}"))
+            .map_err(process_sqlite_error)?;
+        let entries = stmt
+            .query_map((), |row| {
+                Ok((row.get::<_, Data>(0)?, row.get::<_, Data>(1)?))
+            })
+            .map_err(process_sqlite_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(process_sqlite_error)?;
+        Ok(entries)
+    }
+
+    fn set_up_maps(connection: &Connection, desc: &DbDesc) -> storage_core::Result<()> {
+        let tx = connection.unchecked_transaction().map_err(process_sqlite_error)?;
+
+        tx.execute(&queries::create_maps_table_query(), ())
+            .map_err(process_sqlite_error)?;
 
-        // Create a table check sql statement
-        let mut exists_stmt = connection
-            .prepare_cached("SELECT name FROM sqlite_master WHERE type='table' AND name=?")?;
+        let mut exists_stmt = tx
+            .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=?")
+            .map_err(process_sqlite_error)?;
+        let mut get_map_stmt =
+            tx.prepare(&queries::get_map_query()).map_err(process_sqlite_error)?;
+        let mut put_map_stmt =
+            tx.prepare(&queries::put_map_query()).map_err(process_sqlite_error)?;
 
-        // Check if the required tables exist and if needed create them
         for idx in desc.db_map_count().indices() {
-            let table_name = &desc.db_maps()[idx].name();
-            // Check if table is missing
-            let is_missing = exists_stmt
-                .query_row([&table_name], |row| row.get::<usize, String>(0))
-                .optional()?
-                .is_none();
-            // Create the table if needed
-            if is_missing {
-                connection.execute(queries::create_table_query(table_name).as_str(), ())?;
+            let map_desc = &desc.db_maps()[idx];
+            let name = map_desc.name();
+            utils::ensure!(
+                name != queries::MAPS_TABLE_NAME,
+                storage_core::error::Fatal::SchemaMismatch(format!("Map name {name} is reserved")),
+            );
+
+            // Sizes too large to be stored are all as good as unlimited
+            let hint = map_desc.value_size_hint();
+            let hint = [hint.start, hint.end].map(|n| i64::try_from(n).unwrap_or(i64::MAX));
+
+            let recorded = get_map_stmt
+                .query_row([name], |row| {
+                    Ok([row.get::<_, i64>(0)?, row.get::<_, i64>(1)?])
+                })
+                .optional()
+                .map_err(process_sqlite_error)?;
+
+            match recorded {
+                Some(recorded) => {
+                    utils::ensure!(
+                        recorded == hint,
+                        storage_core::error::Fatal::SchemaMismatch(format!(
+                            "Map {name} has been created with a different value size hint"
+                        ))
+                    );
+                }
+                None => {
+                    // Tables created before the properties were recorded are adopted as they are
+                    let is_missing = exists_stmt
+                        .query_row([name], |row| row.get::<usize, String>(0))
+                        .optional()
+                        .map_err(process_sqlite_error)?
+                        .is_none();
+                    if is_missing {
+                        let query = queries::create_table_query(&queries::quote_identifier(name));
+                        tx.execute(&query, ()).map_err(process_sqlite_error)?;
+                    }
+                    put_map_stmt
+                        .execute(rusqlite::params![name, hint[0], hint[1]])
+                        .map_err(process_sqlite_error)?;
+                }
             }
         }
-        drop(exists_stmt);
+
+        drop((exists_stmt, get_map_stmt, put_map_stmt));
+        tx.commit().map_err(process_sqlite_error)
+    }
+
+    /// Open a read-only connection to a database file that has already been set up
+    fn open_reader(&self, path: &Path, desc: &DbDesc) -> rusqlite::Result<Connection> {
+        let flags = OpenFlags::from_iter([
+            OpenFlags::SQLITE_OPEN_FULL_MUTEX,
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+        ]);
+        let connection = Connection::open_with_flags(path, flags)?;
+        self.set_connection_options(&connection, desc)?;
+        Ok(connection)
+    }
+
+    /// Apply the options that are set for each connection separately
+    fn set_connection_options(
+        &self,
+        connection: &Connection,
+        desc: &DbDesc,
+    ) -> rusqlite::Result<()> {
+        let options = &self.options;
+
+        if let Some(cache_size) = options.cache_size {
+            connection.pragma_update(None, "cache_size", cache_size)?;
+        }
+        if let Some(mmap_size) = options.mmap_size {
+            // Checked to fit when validating the options
+            connection.pragma_update(None, "mmap_size", mmap_size as i64)?;
+        }
+        if let Some(busy_timeout) = options.busy_timeout() {
+            connection.busy_timeout(busy_timeout)?;
+        }
+        if let Some(temp_store) = options.temp_store {
+            connection.pragma_update(None, "temp_store", temp_store)?;
+        }
 
         // Set statement cache to fit all the prepared statements we use
         let statement_cap = max(desc.db_map_count().as_usize() * 4, 16);
         connection.set_prepared_statement_cache_capacity(statement_cap);
 
-        Ok(connection)
+        Ok(())
     }
 }
 
-impl backend::Backend for Sqlite {
-    type Impl = SqliteImpl;
+impl Sqlite {
+    /// Open the database, running given check on the connection before any maps are set up
+    fn open_with_check(
+        self,
+        desc: DbDesc,
+        check: impl FnOnce(&Connection) -> storage_core::Result<()>,
+    ) -> storage_core::Result<SqliteImpl> {
+        self.options.validate(self.backend == SqliteStorageMode::InMemory)?;
+        storage_core::util::ensure_plain_maps(&desc)?;
 
-    fn open(self, desc: DbDesc) -> storage_core::Result<Self::Impl> {
         // Attempt to create the parent storage directory if using a file
 
         if let SqliteStorageMode::File(ref path) = self.backend {
@@ -342,11 +800,53 @@ impl backend::Backend for Sqlite {
 
         let queries = desc.db_maps().transform(queries::SqliteQuery::from_desc);
 
-        let connection = self.open_db(desc).map_err(process_sqlite_error)?;
+        let connection = self.open_db(&desc).map_err(process_sqlite_error)?;
+        check(&connection)?;
+        Self::set_up_maps(&connection, &desc)?;
+        let options = EffectiveOptions::query(&connection).map_err(process_sqlite_error)?;
+
+        // The readers are opened only once the writer has set up the database. In-memory
+        // databases in WAL mode have been ruled out when validating the options.
+        let readers = match (&self.options.access_mode, &self.backend) {
+            (AccessMode::Wal { readers }, SqliteStorageMode::File(path)) => {
+                let readers = (0..readers.get())
+                    .map(|_| self.open_reader(path, &desc))
+                    .collect::<rusqlite::Result<_>>()
+                    .map_err(process_sqlite_error)?;
+                let timeout = self.options.busy_timeout().unwrap_or(DEFAULT_WAL_BUSY_TIMEOUT);
+                Some(ReaderPool::new(readers, timeout))
+            }
+            _ => None,
+        };
 
         Ok(SqliteImpl(Arc::new(SqliteConnection {
             connection: Mutex::new(connection),
+            readers,
             queries,
+            options,
         })))
     }
 }
+
+impl backend::Backend for Sqlite {
+    type Impl = SqliteImpl;
+
+    fn open(self, desc: DbDesc) -> storage_core::Result<Self::Impl> {
+        self.open_with_check(desc, |_| Ok(()))
+    }
+
+    fn open_checked(
+        self,
+        desc: DbDesc,
+        checked_map: DbMapId,
+        check: impl FnOnce(&BTreeMap<Data, Data>) -> storage_core::Result<()>,
+    ) -> storage_core::Result<Self::Impl> {
+        let name = desc.db_maps()[checked_map].name().to_owned();
+        self.open_with_check(desc, |connection| {
+            check(&Self::read_existing_map(connection, &name)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod error_tests;
@@ -0,0 +1,384 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Database tuning options
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::OptionalExtension;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use storage_core::error::Fatal;
+
+/// Busy timeout used in the WAL access mode unless set explicitly, see [Options::busy_timeout]
+pub const DEFAULT_WAL_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Implement conversions between an enum and the values of the pragma it represents
+macro_rules! pragma_enum {
+    ($name:ident: $repr:ty { $($variant:ident = $value:expr),* $(,)? }) => {
+        impl ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(match self {
+                    $(Self::$variant => $value.into(),)*
+                })
+            }
+        }
+
+        impl FromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let value = <$repr>::column_result(value)?;
+                $(
+                    if value == $value {
+                        return Ok(Self::$variant);
+                    }
+                )*
+                Err(FromSqlError::InvalidType)
+            }
+        }
+    };
+}
+
+/// How the database is shared between transactions
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum AccessMode {
+    /// The database file is locked exclusively and all transactions run on a single connection,
+    /// one at a time
+    Exclusive,
+
+    /// The database runs in WAL mode, see <https://www.sqlite.org/wal.html>. Read-write
+    /// transactions run on a single writer connection while each read-only transaction takes one
+    /// of `readers` read-only connections, so readers proceed concurrently with each other and
+    /// with the writer. If all the readers are busy, starting a read-only transaction waits for
+    /// one to become available for up to the busy timeout, failing with
+    /// [storage_core::error::Recoverable::TemporarilyUnavailable] after that. Requires the
+    /// database to be stored in a file.
+    Wal { readers: NonZeroUsize },
+}
+
+/// Journal mode, see <https://www.sqlite.org/pragma.html#pragma_journal_mode>
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+pragma_enum!(JournalMode: String {
+    Delete = "delete",
+    Truncate = "truncate",
+    Persist = "persist",
+    Memory = "memory",
+    Wal = "wal",
+    Off = "off",
+});
+
+/// Level of syncing to disk, see <https://www.sqlite.org/pragma.html#pragma_synchronous>
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+pragma_enum!(Synchronous: i64 {
+    Off = 0,
+    Normal = 1,
+    Full = 2,
+    Extra = 3,
+});
+
+/// Where temporary tables and indices are kept, see
+/// <https://www.sqlite.org/pragma.html#pragma_temp_store>
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum TempStore {
+    Default,
+    File,
+    Memory,
+}
+
+pragma_enum!(TempStore: i64 {
+    Default = 0,
+    File = 1,
+    Memory = 2,
+});
+
+/// How free pages are reclaimed, see <https://www.sqlite.org/pragma.html#pragma_auto_vacuum>
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum AutoVacuum {
+    None,
+    Full,
+    Incremental,
+}
+
+pragma_enum!(AutoVacuum: i64 {
+    None = 0,
+    Full = 1,
+    Incremental = 2,
+});
+
+/// Size of the page cache, see <https://www.sqlite.org/pragma.html#pragma_cache_size>
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum CacheSize {
+    /// The cache holds given number of pages
+    Pages(u32),
+    /// The cache takes up given number of KiB
+    KiB(u32),
+}
+
+impl ToSql for CacheSize {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        // Negative values are interpreted as KiB by SQLite
+        let value = match *self {
+            Self::Pages(pages) => i64::from(pages),
+            Self::KiB(kib) => -i64::from(kib),
+        };
+        Ok(value.into())
+    }
+}
+
+impl FromSql for CacheSize {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = i64::column_result(value)?;
+        let size =
+            u32::try_from(value.unsigned_abs()).map_err(|_| FromSqlError::OutOfRange(value))?;
+        Ok(if value < 0 {
+            Self::KiB(size)
+        } else {
+            Self::Pages(size)
+        })
+    }
+}
+
+/// Options of the SQLite backend. Start from the defaults and adjust them using the `with_*`
+/// methods, more options may be added in the future.
+#[derive(Eq, PartialEq, Clone, Debug)]
+#[non_exhaustive]
+pub struct Options {
+    /// If enabled, sets synchronous pragma to OFF, see <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+    /// It should normally only be used in unit tests.
+    pub disable_fsync: bool,
+
+    /// How the database is shared between transactions, exclusive access by default
+    pub access_mode: AccessMode,
+
+    /// Journal mode. Has to be [JournalMode::Wal] or unset in the WAL access mode and
+    /// [JournalMode::Memory] or [JournalMode::Off] for in-memory databases.
+    pub journal_mode: Option<JournalMode>,
+
+    /// Level of syncing to disk. Conflicts with `disable_fsync` unless set to
+    /// [Synchronous::Off].
+    pub synchronous: Option<Synchronous>,
+
+    /// Page size in bytes, a power of two between 512 and 65536. Only takes effect when the
+    /// database is created.
+    pub page_size: Option<u32>,
+
+    /// Size of the page cache of each connection
+    pub cache_size: Option<CacheSize>,
+
+    /// Maximum number of bytes of the database file to access through memory mapping, zero
+    /// disables memory mapping, see <https://www.sqlite.org/pragma.html#pragma_mmap_size>
+    pub mmap_size: Option<u64>,
+
+    /// How long to keep retrying when the database is locked by another connection, with
+    /// millisecond precision. Defaults to [DEFAULT_WAL_BUSY_TIMEOUT] in the WAL access mode,
+    /// where the connections regularly wait for each other.
+    pub busy_timeout: Option<Duration>,
+
+    /// Where temporary tables and indices are kept
+    pub temp_store: Option<TempStore>,
+
+    /// How free pages are reclaimed. Only takes effect when the database is created.
+    pub auto_vacuum: Option<AutoVacuum>,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            disable_fsync: false,
+            access_mode: AccessMode::Exclusive,
+            journal_mode: None,
+            synchronous: None,
+            page_size: None,
+            cache_size: None,
+            mmap_size: None,
+            busy_timeout: None,
+            temp_store: None,
+            auto_vacuum: None,
+        }
+    }
+}
+
+impl Options {
+    /// Set whether syncing to disk is disabled, see [Options::disable_fsync]
+    pub fn with_disable_fsync(mut self, disable_fsync: bool) -> Self {
+        self.disable_fsync = disable_fsync;
+        self
+    }
+
+    /// Set how the database is shared between transactions
+    pub fn with_access_mode(mut self, access_mode: AccessMode) -> Self {
+        self.access_mode = access_mode;
+        self
+    }
+
+    /// Set the journal mode
+    pub fn with_journal_mode(mut self, journal_mode: JournalMode) -> Self {
+        self.journal_mode = Some(journal_mode);
+        self
+    }
+
+    /// Set the level of syncing to disk
+    pub fn with_synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.synchronous = Some(synchronous);
+        self
+    }
+
+    /// Set the page size in bytes
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Set the size of the page cache of each connection
+    pub fn with_cache_size(mut self, cache_size: CacheSize) -> Self {
+        self.cache_size = Some(cache_size);
+        self
+    }
+
+    /// Set the maximum number of bytes to access through memory mapping
+    pub fn with_mmap_size(mut self, mmap_size: u64) -> Self {
+        self.mmap_size = Some(mmap_size);
+        self
+    }
+
+    /// Set how long to keep retrying when the database is locked
+    pub fn with_busy_timeout(mut self, busy_timeout: Duration) -> Self {
+        self.busy_timeout = Some(busy_timeout);
+        self
+    }
+
+    /// Set where temporary tables and indices are kept
+    pub fn with_temp_store(mut self, temp_store: TempStore) -> Self {
+        self.temp_store = Some(temp_store);
+        self
+    }
+
+    /// Set how free pages are reclaimed
+    pub fn with_auto_vacuum(mut self, auto_vacuum: AutoVacuum) -> Self {
+        self.auto_vacuum = Some(auto_vacuum);
+        self
+    }
+
+    /// Check the options are consistent with each other and with where the database is stored
+    pub(crate) fn validate(&self, in_memory: bool) -> storage_core::Result<()> {
+        let invalid = |msg: &str| Err(Fatal::InvalidOptions(msg.into()).into());
+
+        if let AccessMode::Wal { readers: _ } = self.access_mode {
+            if in_memory {
+                return invalid("WAL mode requires the database to be stored in a file");
+            }
+            if self.journal_mode.is_some_and(|mode| mode != JournalMode::Wal) {
+                return invalid("WAL access mode requires the WAL journal mode");
+            }
+        }
+
+        if in_memory
+            && self
+                .journal_mode
+                .is_some_and(|mode| mode != JournalMode::Memory && mode != JournalMode::Off)
+        {
+            return invalid("in-memory databases support only MEMORY or OFF journal modes");
+        }
+
+        if self.disable_fsync && self.synchronous.is_some_and(|sync| sync != Synchronous::Off) {
+            return invalid("fsync disabled but synchronous level set");
+        }
+
+        if let Some(page_size) = self.page_size {
+            if !page_size.is_power_of_two() || !(512..=65536).contains(&page_size) {
+                return invalid("page size not a power of two between 512 and 65536");
+            }
+        }
+
+        if self.mmap_size.is_some_and(|size| i64::try_from(size).is_err()) {
+            return invalid("mmap size too large");
+        }
+
+        if self.busy_timeout.is_some_and(|timeout| timeout.as_millis() > i32::MAX as u128) {
+            return invalid("busy timeout too long");
+        }
+
+        Ok(())
+    }
+
+    /// The busy timeout requested by the options, if any
+    pub(crate) fn busy_timeout(&self) -> Option<Duration> {
+        match self.access_mode {
+            AccessMode::Exclusive => self.busy_timeout,
+            AccessMode::Wal { readers: _ } => {
+                Some(self.busy_timeout.unwrap_or(DEFAULT_WAL_BUSY_TIMEOUT))
+            }
+        }
+    }
+
+    /// The synchronous level requested by the options, if any
+    pub(crate) fn synchronous(&self) -> Option<Synchronous> {
+        if self.disable_fsync {
+            Some(Synchronous::Off)
+        } else {
+            self.synchronous
+        }
+    }
+}
+
+/// Settings in effect for an open database, as reported by SQLite
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EffectiveOptions {
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+    pub page_size: u32,
+    pub cache_size: CacheSize,
+    pub mmap_size: u64,
+    pub busy_timeout: Duration,
+    pub temp_store: TempStore,
+    pub auto_vacuum: AutoVacuum,
+}
+
+impl EffectiveOptions {
+    /// Read the settings of given connection
+    pub(crate) fn query(connection: &rusqlite::Connection) -> rusqlite::Result<Self> {
+        fn get<T: FromSql>(connection: &rusqlite::Connection, name: &str) -> rusqlite::Result<T> {
+            connection.pragma_query_value(None, name, |row| row.get(0))
+        }
+        // Not reported for databases that cannot be memory mapped, such as in-memory ones
+        let mmap_size = get(connection, "mmap_size").optional()?.unwrap_or(0);
+        Ok(Self {
+            journal_mode: get(connection, "journal_mode")?,
+            synchronous: get(connection, "synchronous")?,
+            page_size: get(connection, "page_size")?,
+            cache_size: get(connection, "cache_size")?,
+            mmap_size,
+            busy_timeout: Duration::from_millis(get(connection, "busy_timeout")?),
+            temp_store: get(connection, "temp_store")?,
+            auto_vacuum: get(connection, "auto_vacuum")?,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connections used by transactions
+
+use rusqlite::Connection;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use storage_core::error::{Fatal, Recoverable};
+
+/// Pool of read-only connections, each serving one read-only transaction at a time
+pub struct ReaderPool {
+    /// Connections not currently in use
+    idle: Mutex<Vec<Connection>>,
+
+    /// Signalled whenever a connection is returned to the pool
+    returned: Condvar,
+
+    /// How long to wait for a connection to be returned if all are in use
+    timeout: Duration,
+}
+
+impl ReaderPool {
+    pub fn new(connections: Vec<Connection>, timeout: Duration) -> Self {
+        Self {
+            idle: Mutex::new(connections),
+            returned: Condvar::new(),
+            timeout,
+        }
+    }
+
+    /// Take a connection out of the pool, waiting for one to be returned if all are in use.
+    /// Fails with [Recoverable::TemporarilyUnavailable] if none is returned in time.
+    pub fn acquire(&self) -> storage_core::Result<PooledConnection<'_>> {
+        let idle = self.idle.lock().map_err(|e| Fatal::InternalError(e.to_string()))?;
+        let (mut idle, _) = self
+            .returned
+            .wait_timeout_while(idle, self.timeout, |idle| idle.is_empty())
+            .map_err(|e| Fatal::InternalError(e.to_string()))?;
+        let connection = idle.pop().ok_or(Recoverable::TemporarilyUnavailable)?;
+        Ok(PooledConnection {
+            pool: self,
+            connection: Some(connection),
+        })
+    }
+}
+
+/// Connection taken out of a [ReaderPool], returned to it once dropped
+pub struct PooledConnection<'m> {
+    pool: &'m ReaderPool,
+    connection: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection.as_ref().expect("connection to be present until dropped")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let (Some(connection), Ok(mut idle)) = (self.connection.take(), self.pool.idle.lock()) {
+            idle.push(connection);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+/// Connection a transaction runs on
+pub enum TxConnection<'m> {
+    /// A connection shared by transactions, locked for the duration of this one
+    Locked(MutexGuard<'m, Connection>),
+
+    /// A connection taken from the reader pool
+    Pooled(PooledConnection<'m>),
+}
+
+impl Deref for TxConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            Self::Locked(connection) => connection,
+            Self::Pooled(connection) => connection,
+        }
+    }
+}
@@ -13,35 +13,134 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::ops::Bound;
+
 use storage_core::{DbMapDesc, DbMapsData};
 
-/// Returns an SQL query to create a table
+/// Name of the table recording the key/value databases and their properties
+pub const MAPS_TABLE_NAME: &str = "__storage_db_maps";
+
+/// Quote a name so that it can be used as an identifier in SQL queries, whatever it contains
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Returns an SQL query to create a table, given its quoted name
 #[inline]
 pub fn create_table_query(table_name: &str) -> String {
     format!("CREATE TABLE {table_name}(key BLOB PRIMARY KEY NOT NULL, value BLOB NOT NULL)")
 }
 
+/// Returns an SQL query to create the table recording the key/value databases, if missing
+pub fn create_maps_table_query() -> String {
+    let table_name = quote_identifier(MAPS_TABLE_NAME);
+    format!(
+        "CREATE TABLE IF NOT EXISTS {table_name}(name TEXT PRIMARY KEY NOT NULL, \
+        value_size_min INTEGER NOT NULL, value_size_max INTEGER NOT NULL)"
+    )
+}
+
+/// Returns an SQL query to get the recorded properties of a key/value database by its name
+pub fn get_map_query() -> String {
+    let table_name = quote_identifier(MAPS_TABLE_NAME);
+    format!("SELECT value_size_min, value_size_max FROM {table_name} WHERE name = ?")
+}
+
+/// Returns an SQL query to record the properties of a key/value database
+pub fn put_map_query() -> String {
+    let table_name = quote_identifier(MAPS_TABLE_NAME);
+    format!("INSERT INTO {table_name} values(?, ?, ?)")
+}
+
+/// Returns the `WHERE` clause restricting keys to the range with given bound kinds, if any.
+///
+/// The clause takes one parameter for each bounded end of the range, start bound first.
+fn range_where_clause<T>(start: Bound<T>, end: Bound<T>) -> String {
+    let start_cond = match start {
+        Bound::Included(_) => Some("key >= ?"),
+        Bound::Excluded(_) => Some("key > ?"),
+        Bound::Unbounded => None,
+    };
+    let end_cond = match end {
+        Bound::Included(_) => Some("key <= ?"),
+        Bound::Excluded(_) => Some("key < ?"),
+        Bound::Unbounded => None,
+    };
+    let conds: Vec<_> = start_cond.into_iter().chain(end_cond).collect();
+    if conds.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conds.join(" AND "))
+    }
+}
+
+/// Returns an SQL query to iterate over the key range with given bound kinds.
+///
+/// The query takes one parameter for each bounded end of the range, start bound first. The
+/// entries are returned in descending key order if `descending` is set. At most `limit` entries
+/// are returned if given.
+pub fn range_iter_query<T>(
+    table_name: &str,
+    start: Bound<T>,
+    end: Bound<T>,
+    descending: bool,
+    limit: Option<usize>,
+) -> String {
+    let where_clause = range_where_clause(start, end);
+    let order = if descending { "DESC" } else { "ASC" };
+    let limit_clause = limit.map_or_else(String::new, |limit| format!(" LIMIT {limit}"));
+    format!("SELECT key, value FROM {table_name}{where_clause} ORDER BY key {order}{limit_clause}")
+}
+
+/// Returns an SQL query to iterate over the keys in the key range with given bound kinds.
+///
+/// The query takes one parameter for each bounded end of the range, start bound first. The keys
+/// are returned in ascending order.
+pub fn range_iter_keys_query<T>(table_name: &str, start: Bound<T>, end: Bound<T>) -> String {
+    let where_clause = range_where_clause(start, end);
+    format!("SELECT key FROM {table_name}{where_clause} ORDER BY key")
+}
+
+/// Returns an SQL query to delete all entries in the key range with given bound kinds.
+///
+/// The query takes one parameter for each bounded end of the range, start bound first.
+pub fn range_delete_query<T>(table_name: &str, start: Bound<T>, end: Bound<T>) -> String {
+    let where_clause = range_where_clause(start, end);
+    format!("DELETE FROM {table_name}{where_clause}")
+}
+
 /// SQL queries that are customized per an individual key/value database
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub struct SqliteQuery {
+    /// Name of the table holding the key/value database, quoted for use in queries
+    pub table_name: String,
     /// Used for the get operation
     pub get_query: String,
-    /// Used for the prefix iter operation
-    pub prefix_iter_query: String,
     /// Used for the put operation
     pub put_query: String,
     /// Used for the delete operation
     pub delete_query: String,
+    /// Used for the conditional insert operation
+    pub insert_if_absent_query: String,
+    /// Used for the conditional update operation
+    pub update_if_equal_query: String,
+    /// Used for the conditional delete operation
+    pub delete_if_equal_query: String,
 }
 
 impl SqliteQuery {
     pub fn from_desc(desc: &DbMapDesc) -> Self {
-        let name = desc.name();
+        let name = quote_identifier(desc.name());
         Self {
+            table_name: name.clone(),
             get_query: format!("SELECT value FROM {name} WHERE key = ?"),
-            prefix_iter_query: format!("SELECT key, value FROM {name} ORDER BY key"),
             put_query: format!("INSERT or REPLACE into {name} values(?, ?)"),
             delete_query: format!("DELETE FROM {name} WHERE key = ?"),
+            insert_if_absent_query: format!("INSERT or IGNORE into {name} values(?, ?)"),
+            update_if_equal_query: format!(
+                "UPDATE {name} SET value = ? WHERE key = ? AND value = ?"
+            ),
+            delete_if_equal_query: format!("DELETE FROM {name} WHERE key = ? AND value = ?"),
         }
     }
 }
@@ -28,9 +28,8 @@ fn main() {
                 .as_ref()
                 .to_path_buf()
                 .join("database.sqlite");
-            Sqlite::new(db_file_path).with_options(storage_sqlite::Options {
-                disable_fsync: true,
-            })
+            Sqlite::new(db_file_path)
+                .with_options(storage_sqlite::Options::default().with_disable_fsync(true))
         }
     };
 
@@ -0,0 +1,49 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for errors reported when opening a database
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::error::Recoverable;
+use storage_sqlite::Sqlite;
+
+#[test]
+fn database_in_use() {
+    let test_root = test_utils::test_root!("errors-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("in_use").as_ref().join("database.sqlite");
+
+    // The first instance holds an exclusive lock on the file
+    let store = Sqlite::new(&db_file_path).open(desc(1)).unwrap();
+    let result = Sqlite::new(&db_file_path).open(desc(1));
+    assert_eq!(
+        result.err(),
+        Some(Recoverable::TemporarilyUnavailable.into())
+    );
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn not_a_database() {
+    let test_root = test_utils::test_root!("errors-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("garbage").as_ref().join("database.sqlite");
+
+    std::fs::write(&db_file_path, [0x5a; 4096]).unwrap();
+    let result = Sqlite::new(&db_file_path).open(desc(1));
+    assert_eq!(result.err(), Some(Recoverable::DbInit.into()));
+
+    test_root.delete();
+}
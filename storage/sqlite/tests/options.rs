@@ -0,0 +1,109 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for applying the database tuning options
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::error::Fatal;
+use storage_sqlite::{
+    AccessMode, AutoVacuum, CacheSize, EffectiveOptions, JournalMode, Options, Sqlite, Synchronous,
+    TempStore, DEFAULT_WAL_BUSY_TIMEOUT,
+};
+
+#[test]
+fn options_applied() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("applied").as_ref().join("database.sqlite");
+
+    let options = Options::default()
+        .with_journal_mode(JournalMode::Truncate)
+        .with_synchronous(Synchronous::Extra)
+        .with_page_size(8192)
+        .with_cache_size(CacheSize::KiB(4096))
+        .with_mmap_size(1 << 20)
+        .with_busy_timeout(Duration::from_millis(250))
+        .with_temp_store(TempStore::Memory)
+        .with_auto_vacuum(AutoVacuum::Incremental);
+    let store = Sqlite::new(db_file_path).with_options(options).open(desc(1)).unwrap();
+
+    let expected = EffectiveOptions {
+        journal_mode: JournalMode::Truncate,
+        synchronous: Synchronous::Extra,
+        page_size: 8192,
+        cache_size: CacheSize::KiB(4096),
+        mmap_size: 1 << 20,
+        busy_timeout: Duration::from_millis(250),
+        temp_store: TempStore::Memory,
+        auto_vacuum: AutoVacuum::Incremental,
+    };
+    assert_eq!(store.options(), &expected);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn wal_journal_mode_in_effect() {
+    let test_root = test_utils::test_root!("options-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("wal").as_ref().join("database.sqlite");
+
+    let options = Options::default().with_disable_fsync(true).with_access_mode(AccessMode::Wal {
+        readers: NonZeroUsize::new(1).expect("non-zero reader count"),
+    });
+    let store = Sqlite::new(db_file_path).with_options(options).open(desc(1)).unwrap();
+
+    assert_eq!(store.options().journal_mode, JournalMode::Wal);
+    assert_eq!(store.options().synchronous, Synchronous::Off);
+    assert_eq!(store.options().busy_timeout, DEFAULT_WAL_BUSY_TIMEOUT);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn in_memory_defaults() {
+    let store = Sqlite::new_in_memory().open(desc(1)).unwrap();
+    assert_eq!(store.options().journal_mode, JournalMode::Memory);
+}
+
+#[test]
+fn invalid_options_rejected() {
+    let check = |options: Options, msg: &str| {
+        let result = Sqlite::new_in_memory().with_options(options).open(desc(1));
+        assert_eq!(result.err(), Some(Fatal::InvalidOptions(msg.into()).into()));
+    };
+
+    let page_size_msg = "page size not a power of two between 512 and 65536";
+    check(Options::default().with_page_size(1000), page_size_msg);
+    check(Options::default().with_page_size(1 << 17), page_size_msg);
+    check(
+        Options::default().with_disable_fsync(true).with_synchronous(Synchronous::Full),
+        "fsync disabled but synchronous level set",
+    );
+    check(
+        Options::default().with_journal_mode(JournalMode::Wal),
+        "in-memory databases support only MEMORY or OFF journal modes",
+    );
+    check(
+        Options::default().with_mmap_size(u64::MAX),
+        "mmap size too large",
+    );
+    check(
+        Options::default().with_busy_timeout(Duration::from_secs(u64::MAX)),
+        "busy timeout too long",
+    );
+}
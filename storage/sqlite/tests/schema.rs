@@ -0,0 +1,73 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for checking the recorded properties of key/value databases
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::error::Fatal;
+use storage_sqlite::Sqlite;
+
+fn desc_with_hint(value_size_hint: std::ops::Range<usize>) -> DbDesc {
+    let maps = [DbMapDesc::new("plain"), DbMapDesc::new_with_details("hinted", value_size_hint)];
+    storage_core::types::construct::db_desc(maps.into_iter())
+}
+
+#[test]
+fn value_size_hint_drift() {
+    let test_root = test_utils::test_root!("schema-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("drift").as_ref().join("database.sqlite");
+
+    let store = Sqlite::new(&db_file_path).open(desc_with_hint(32..33)).unwrap();
+    drop(store);
+
+    // Reopening with the same description is fine
+    let store = Sqlite::new(&db_file_path).open(desc_with_hint(32..33)).unwrap();
+    drop(store);
+
+    // A different value size hint is detected
+    let result = Sqlite::new(&db_file_path).open(desc_with_hint(0..usize::MAX));
+    let expected = Fatal::SchemaMismatch(
+        "Map hinted has been created with a different value size hint".into(),
+    );
+    assert_eq!(result.err(), Some(expected.into()));
+
+    test_root.delete();
+}
+
+#[test]
+fn reserved_map_name() {
+    let maps = [DbMapDesc::new("__storage_db_maps")];
+    let desc = storage_core::types::construct::db_desc(maps.into_iter());
+    let result = Sqlite::new_in_memory().open(desc);
+    let expected = Fatal::SchemaMismatch("Map name __storage_db_maps is reserved".into());
+    assert_eq!(result.err(), Some(expected.into()));
+}
+
+#[test]
+fn unsupported_map_layout() {
+    let check = |map_desc: DbMapDesc| {
+        let name = map_desc.name().to_owned();
+        let desc = storage_core::types::construct::db_desc([map_desc].into_iter());
+        let result = Sqlite::new_in_memory().open(desc);
+        let expected = Fatal::SchemaMismatch(format!(
+            "Map {name} needs a key order or duplicate values not supported by the backend"
+        ));
+        assert_eq!(result.err(), Some(expected.into()));
+    };
+
+    check(DbMapDesc::new("reverse").with_key_order(storage_core::KeyOrder::Reverse));
+    check(DbMapDesc::new("integer").with_key_order(storage_core::KeyOrder::Integer));
+    check(DbMapDesc::new("duplicates").with_duplicate_values());
+}
@@ -0,0 +1,111 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests specific to the WAL access mode
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use storage_backend_test_suite::prelude::*;
+use storage_core::error::{Fatal, Recoverable};
+use storage_sqlite::{AccessMode, Options, Sqlite};
+
+fn wal_options(readers: usize) -> Options {
+    Options::default().with_disable_fsync(true).with_access_mode(AccessMode::Wal {
+        readers: NonZeroUsize::new(readers).expect("non-zero reader count"),
+    })
+}
+
+#[test]
+fn readers_do_not_block_writer() {
+    let test_root = test_utils::test_root!("wal-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("readers").as_ref().join("database.sqlite");
+    let store = Sqlite::new(db_file_path).with_options(wal_options(2)).open(desc(1)).unwrap();
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"key".to_vec(), b"old".to_vec()).unwrap();
+    dbtx.commit().unwrap();
+
+    // Start reading, then modify the database while the reader is still open. With an exclusive
+    // connection, starting the read-write transaction would wait for the reader to finish.
+    let reader = store.transaction_ro().unwrap();
+    assert_eq!(
+        reader.get(MAPID.0, b"key").unwrap().as_deref(),
+        Some(&b"old"[..])
+    );
+
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.put(MAPID.0, b"key".to_vec(), b"new".to_vec()).unwrap();
+    dbtx.commit().unwrap();
+
+    // The reader keeps seeing its snapshot while new readers see the modification
+    let new_reader = store.transaction_ro().unwrap();
+    assert_eq!(
+        new_reader.get(MAPID.0, b"key").unwrap().as_deref(),
+        Some(&b"new"[..])
+    );
+    assert_eq!(
+        reader.get(MAPID.0, b"key").unwrap().as_deref(),
+        Some(&b"old"[..])
+    );
+    drop(new_reader);
+    drop(reader);
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn readers_are_reused() {
+    let test_root = test_utils::test_root!("wal-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("reuse").as_ref().join("database.sqlite");
+    let store = Sqlite::new(db_file_path).with_options(wal_options(1)).open(desc(1)).unwrap();
+
+    // A single reader serves any number of read-only transactions, one after another
+    for _ in 0..3 {
+        let dbtx = store.transaction_ro().unwrap();
+        assert_eq!(dbtx.get(MAPID.0, b"key"), Ok(None));
+    }
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn busy_readers_time_out() {
+    let test_root = test_utils::test_root!("wal-tests").unwrap();
+    let db_file_path = test_root.fresh_test_dir("timeout").as_ref().join("database.sqlite");
+    let options = wal_options(1).with_busy_timeout(Duration::from_millis(10));
+    let store = Sqlite::new(db_file_path).with_options(options).open(desc(1)).unwrap();
+
+    // The only reader is taken, another read-only transaction gives up waiting for it
+    let reader = store.transaction_ro().unwrap();
+    assert_eq!(
+        store.transaction_ro().err(),
+        Some(Recoverable::TemporarilyUnavailable.into())
+    );
+    drop(reader);
+    assert!(store.transaction_ro().is_ok());
+
+    drop(store);
+    test_root.delete();
+}
+
+#[test]
+fn in_memory_rejected() {
+    let result = Sqlite::new_in_memory().with_options(wal_options(1)).open(desc(1));
+    let expected =
+        Fatal::InvalidOptions("WAL mode requires the database to be stored in a file".into());
+    assert_eq!(result.err(), Some(expected.into()));
+}
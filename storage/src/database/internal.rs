@@ -15,10 +15,10 @@
 
 //! Internal database implementation utils
 
-use std::borrow::Cow;
+use std::{borrow::Cow, ops::Bound};
 
 use crate::schema;
-use serialization::{encoded::Encoded, EncodeLike};
+use serialization::{encoded::Encoded, Encode, EncodeLike};
 use storage_core::{
     backend::{self, ReadOps},
     Backend, DbMapId,
@@ -48,37 +48,82 @@ pub fn get<DbMap: schema::DbMap, Tx: ReadOps, K: EncodeLike<DbMap::Key>>(
     key.using_encoded(|key| dbtx.get(map_id, key).map(|x| x.map(Encoded::from_bytes_unchecked)))
 }
 
-/// Iterator over DB map entries
-pub trait EntryIterator<DbMap: schema::DbMap>:
-    Iterator<Item = (DbMap::Key, Encoded<Vec<u8>, DbMap::Value>)>
+/// Iterator over DB map entries. The values may be borrowed from the database.
+pub trait EntryIterator<'i, DbMap: schema::DbMap>:
+    Iterator<Item = crate::Result<(DbMap::Key, Encoded<Cow<'i, [u8]>, DbMap::Value>)>>
 {
 }
 
-impl<DbMap: schema::DbMap, I: Iterator<Item = (DbMap::Key, Encoded<Vec<u8>, DbMap::Value>)>>
-    EntryIterator<DbMap> for I
+impl<'i, DbMap, I> EntryIterator<'i, DbMap> for I
+where
+    DbMap: schema::DbMap,
+    I: Iterator<Item = crate::Result<(DbMap::Key, Encoded<Cow<'i, [u8]>, DbMap::Value>)>>,
 {
 }
 
+/// Decode the key of a raw entry and wrap the value as a SCALE-encoded object
+#[allow(clippy::type_complexity)]
+fn decode_entry<DbMap: schema::DbMap>(
+    (k, v): backend::Entry<'_>,
+) -> (DbMap::Key, Encoded<Cow<'_, [u8]>, DbMap::Value>) {
+    (
+        Encoded::from_bytes_unchecked(k).decode(),
+        Encoded::from_bytes_unchecked(v),
+    )
+}
+
 pub fn prefix_iter<DbMap: schema::DbMap, Tx: ReadOps>(
     dbtx: &Tx,
     map_id: DbMapId,
     prefix: Vec<u8>,
-) -> crate::Result<impl '_ + EntryIterator<DbMap>> {
-    dbtx.prefix_iter(map_id, prefix).map(|iter| {
-        iter.map(|(k, v)| {
-            (
-                Encoded::from_bytes_unchecked(k).decode(),
-                Encoded::from_bytes_unchecked(v),
-            )
-        })
-    })
+) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+    dbtx.prefix_iter(map_id, prefix)
+        .map(|iter| iter.map(|item| item.map(decode_entry::<DbMap>)))
+}
+
+pub fn prefix_iter_rev<DbMap: schema::DbMap, Tx: ReadOps>(
+    dbtx: &Tx,
+    map_id: DbMapId,
+    prefix: Vec<u8>,
+) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+    dbtx.prefix_iter_rev(map_id, prefix)
+        .map(|iter| iter.map(|item| item.map(decode_entry::<DbMap>)))
+}
+
+pub fn range_iter<DbMap: schema::DbMap, Tx: ReadOps>(
+    dbtx: &Tx,
+    map_id: DbMapId,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+    dbtx.range_iter(map_id, start, end)
+        .map(|iter| iter.map(|item| item.map(decode_entry::<DbMap>)))
+}
+
+pub fn range_iter_rev<DbMap: schema::DbMap, Tx: ReadOps>(
+    dbtx: &Tx,
+    map_id: DbMapId,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+    dbtx.range_iter_rev(map_id, start, end)
+        .map(|iter| iter.map(|item| item.map(decode_entry::<DbMap>)))
+}
+
+/// Encode the key in a range bound
+pub fn encode_bound<K: Encode>(bound: Bound<&K>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.encode()),
+        Bound::Excluded(key) => Bound::Excluded(key.encode()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
 }
 
 pub fn prefix_iter_keys<DbMap: schema::DbMap, Tx: ReadOps>(
     dbtx: &Tx,
     map_id: DbMapId,
     prefix: Vec<u8>,
-) -> crate::Result<impl '_ + Iterator<Item = DbMap::Key>> {
-    dbtx.prefix_iter(map_id, prefix)
-        .map(|iter| iter.map(|(k, _v)| Encoded::from_bytes_unchecked(k).decode()))
+) -> crate::Result<impl '_ + Iterator<Item = crate::Result<DbMap::Key>>> {
+    dbtx.prefix_iter_keys(map_id, prefix)
+        .map(|iter| iter.map(|k| k.map(|k| Encoded::from_bytes_unchecked(k).decode())))
 }
@@ -0,0 +1,387 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Schema versioning and migrations
+//!
+//! Each database opened through [crate::Storage] records the version of the schema that wrote it
+//! in a reserved metadata map. The schema version is the number of migrations the code knows
+//! about. When the database is opened, the migrations between the recorded version and the
+//! latest one run in order, all inside a single read-write transaction. A database written by
+//! a newer version of the schema is refused.
+//!
+//! A database with no recorded version is treated as version 0 if it contains any data. An empty
+//! one is considered to be freshly created and gets the latest version straight away.
+//!
+//! Along with the version, the fingerprint of the schema is recorded, see
+//! [crate::schema::Fingerprint]. A database with the latest version but a different fingerprint,
+//! i.e. one where the maps have changed without a migration, fails to open with
+//! [Fatal::SchemaMismatch] describing the changed maps.
+//!
+//! A database that fails to open is refused before any of the maps missing from it are created,
+//! see [Backend::open_checked]. The metadata is only written when it changes.
+
+use std::{collections::BTreeMap, ops::Bound};
+
+use serialization::{Decode, DecodeAll, Encode};
+use storage_core::{
+    backend::{self, BackendImpl, ReadOps, WriteOps},
+    error::Fatal,
+    Backend, Data, DbMapDesc, DbMapId,
+};
+
+use super::raw::MapContents;
+use crate::schema::{Fingerprint, Schema};
+
+/// Name of the reserved map holding the database metadata
+pub const METADATA_MAP_NAME: &str = "__storage_metadata";
+
+/// Metadata key under which the schema version is stored
+const VERSION_KEY: &[u8] = b"version";
+
+/// Metadata key under which the schema fingerprint is stored
+const FINGERPRINT_KEY: &[u8] = b"fingerprint";
+
+/// Number of entries migrations transforming whole maps read from the database at a time
+const BATCH_SIZE: usize = 1000;
+
+/// One step in the evolution of the database schema, upgrading the database from the version
+/// given by the position of the migration in the list passed to
+/// [crate::Storage::new_with_migrations] to the next one
+///
+/// Migrations only change the contents of maps. Removing or renaming a map in the database is
+/// not supported, a map that is no longer used can only be emptied, see
+/// [MigrationTx::move_entries] and [MigrationTx::clear_map].
+pub trait Migration {
+    /// Maps that the migration accesses but that are no longer part of the current schema, e.g.
+    /// maps whose entries are moved elsewhere. They are opened along with the schema maps.
+    fn legacy_maps(&self) -> Vec<DbMapDesc> {
+        Vec::new()
+    }
+
+    /// Upgrade the database contents
+    fn migrate(&self, tx: &mut MigrationTx<'_>) -> crate::Result<()>;
+}
+
+/// Raw read-write transaction operations needed by migrations, in an object-safe form
+trait RawTxRw {
+    fn get(&self, map_id: DbMapId, key: &[u8]) -> crate::Result<Option<Data>>;
+    fn contents(&self, map_id: DbMapId) -> crate::Result<MapContents>;
+    fn batch_after(
+        &self,
+        map_id: DbMapId,
+        after: Option<&[u8]>,
+    ) -> crate::Result<Vec<(Data, Data)>>;
+    fn put(&mut self, map_id: DbMapId, key: Data, val: Data) -> crate::Result<()>;
+    fn del(&mut self, map_id: DbMapId, key: &[u8]) -> crate::Result<()>;
+    fn clear(&mut self, map_id: DbMapId) -> crate::Result<()>;
+}
+
+impl<T: backend::TxRw> RawTxRw for T {
+    fn get(&self, map_id: DbMapId, key: &[u8]) -> crate::Result<Option<Data>> {
+        ReadOps::get(self, map_id, key).map(|val| val.map(|val| val.into_owned()))
+    }
+
+    fn contents(&self, map_id: DbMapId) -> crate::Result<MapContents> {
+        self.prefix_iter(map_id, Vec::new())?
+            .map(|item| item.map(|(k, v)| (k.into_owned(), v.into_owned())))
+            .collect()
+    }
+
+    fn batch_after(
+        &self,
+        map_id: DbMapId,
+        after: Option<&[u8]>,
+    ) -> crate::Result<Vec<(Data, Data)>> {
+        let start = after.map_or(Bound::Unbounded, |key| Bound::Excluded(key.to_vec()));
+        let mut batch: Vec<(Data, Data)> = Vec::new();
+        for item in self.range_iter(map_id, start, Bound::Unbounded)? {
+            let (key, val) = item?;
+            // Keep all values of the last key together so the next batch can start after it
+            if batch.len() >= BATCH_SIZE && batch.last().map_or(true, |(last, _)| *last != *key) {
+                break;
+            }
+            batch.push((key.into_owned(), val.into_owned()));
+        }
+        Ok(batch)
+    }
+
+    fn put(&mut self, map_id: DbMapId, key: Data, val: Data) -> crate::Result<()> {
+        WriteOps::put(self, map_id, key, val)
+    }
+
+    fn del(&mut self, map_id: DbMapId, key: &[u8]) -> crate::Result<()> {
+        WriteOps::del(self, map_id, key)
+    }
+
+    fn clear(&mut self, map_id: DbMapId) -> crate::Result<()> {
+        WriteOps::clear(self, map_id)
+    }
+}
+
+/// Transaction in which migrations run. Maps are addressed by name, both those in the current
+/// schema and the legacy ones declared by the migrations.
+pub struct MigrationTx<'a> {
+    dbtx: &'a mut dyn RawTxRw,
+    map_ids: &'a BTreeMap<String, DbMapId>,
+}
+
+impl<'a> MigrationTx<'a> {
+    fn map_id(&self, map: &str) -> crate::Result<DbMapId> {
+        self.map_ids.get(map).copied().ok_or_else(|| {
+            Fatal::InternalError(format!("Map {map} not known to the migration")).into()
+        })
+    }
+
+    /// Get the raw value associated with given key
+    pub fn get(&self, map: &str, key: &[u8]) -> crate::Result<Option<Data>> {
+        self.dbtx.get(self.map_id(map)?, key)
+    }
+
+    /// Get all raw entries of given map. The whole map is loaded into memory.
+    pub fn contents(&self, map: &str) -> crate::Result<MapContents> {
+        self.dbtx.contents(self.map_id(map)?)
+    }
+
+    /// Insert a raw value associated with given key, replacing the previous one if any
+    pub fn put(&mut self, map: &str, key: Data, val: Data) -> crate::Result<()> {
+        self.dbtx.put(self.map_id(map)?, key, val)
+    }
+
+    /// Remove the value associated with given key
+    pub fn del(&mut self, map: &str, key: &[u8]) -> crate::Result<()> {
+        self.dbtx.del(self.map_id(map)?, key)
+    }
+
+    /// Apply given function to each raw entry of given map, in key order. The entries are read in
+    /// bounded batches, so the function may write to the map being traversed as long as it only
+    /// touches keys that have already been visited.
+    fn for_each_entry(
+        &mut self,
+        map_id: DbMapId,
+        mut func: impl FnMut(&mut dyn RawTxRw, Data, Data) -> crate::Result<()>,
+    ) -> crate::Result<()> {
+        let mut after: Option<Data> = None;
+        loop {
+            let batch = self.dbtx.batch_after(map_id, after.as_deref())?;
+            let last = match batch.last() {
+                Some((key, _)) => key.clone(),
+                None => return Ok(()),
+            };
+            for (key, val) in batch {
+                func(self.dbtx, key, val)?;
+            }
+            after = Some(last);
+        }
+    }
+
+    /// Move all entries from one map to another, leaving the former empty. Entries already
+    /// present in the target map are overwritten if the source map has the same key. The source
+    /// map itself stays in the database, see [Migration].
+    pub fn move_entries(&mut self, from: &str, to: &str) -> crate::Result<()> {
+        let (from, to) = (self.map_id(from)?, self.map_id(to)?);
+        if from != to {
+            self.for_each_entry(from, |dbtx, key, val| dbtx.put(to, key, val))?;
+            self.dbtx.clear(from)?;
+        }
+        Ok(())
+    }
+
+    /// Remove all entries from given map. The map itself stays in the database, see [Migration].
+    pub fn clear_map(&mut self, map: &str) -> crate::Result<()> {
+        self.dbtx.clear(self.map_id(map)?)
+    }
+
+    /// Replace each raw value in given map by the result of applying a function to its key and
+    /// the original value
+    pub fn reencode_raw(
+        &mut self,
+        map: &str,
+        mut func: impl FnMut(&[u8], Data) -> crate::Result<Data>,
+    ) -> crate::Result<()> {
+        let map_id = self.map_id(map)?;
+        self.for_each_entry(map_id, |dbtx, key, val| {
+            let val = func(&key, val)?;
+            dbtx.put(map_id, key, val)
+        })
+    }
+
+    /// Decode each value in given map as `Old`, convert it using given function and store it
+    /// encoded as `New`. Values that fail to decode are reported as database corruption.
+    pub fn reencode<Old: Decode, New: Encode>(
+        &mut self,
+        map: &str,
+        mut func: impl FnMut(Old) -> New,
+    ) -> crate::Result<()> {
+        self.reencode_raw(map, |_key, val| {
+            let old = Old::decode_all(&mut val.as_slice()).map_err(|_| Fatal::DatabaseCorrupted)?;
+            Ok(func(old).encode())
+        })
+    }
+}
+
+/// Get a metadata entry
+fn get_metadata<T: Decode>(
+    dbtx: &impl ReadOps,
+    map_id: DbMapId,
+    key: &[u8],
+) -> crate::Result<Option<T>> {
+    decode_metadata(dbtx.get(map_id, key)?.as_deref())
+}
+
+/// Decode a metadata entry, if present
+fn decode_metadata<T: Decode>(val: Option<&[u8]>) -> crate::Result<Option<T>> {
+    val.map(|mut val| T::decode_all(&mut val).map_err(|_| Fatal::DatabaseCorrupted.into()))
+        .transpose()
+}
+
+/// Check the database with given recorded schema version and fingerprint can be opened using
+/// the current schema
+fn check_recorded(
+    version: u32,
+    recorded_fingerprint: Option<&Fingerprint>,
+    latest_version: u32,
+    fingerprint: &Fingerprint,
+) -> crate::Result<()> {
+    utils::ensure!(
+        version <= latest_version,
+        Fatal::SchemaVersionTooNew(version, latest_version)
+    );
+    if version == latest_version {
+        if let Some(recorded_fingerprint) = recorded_fingerprint {
+            let diff = recorded_fingerprint.diff(fingerprint);
+            utils::ensure!(
+                diff.is_empty(),
+                Fatal::SchemaMismatch(format!("Maps changed: {}", diff.join("; ")))
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Open the database for given schema, bringing its contents up to date using given migrations
+pub(crate) fn open<B: Backend, Sch: Schema>(
+    backend: B,
+    migrations: &[&dyn Migration],
+) -> crate::Result<B::Impl> {
+    let schema_len = Sch::desc_iter().count();
+    utils::ensure!(
+        Sch::desc_iter().all(|desc| desc.name() != METADATA_MAP_NAME),
+        Fatal::SchemaMismatch(format!("Map name {METADATA_MAP_NAME} is reserved"))
+    );
+
+    // Schema maps keep their indices, followed by the metadata map and then the legacy maps
+    let mut map_descs: Vec<DbMapDesc> = Sch::desc_iter().collect();
+    map_descs.push(DbMapDesc::new(METADATA_MAP_NAME));
+    for desc in migrations.iter().flat_map(|migration| migration.legacy_maps()) {
+        if map_descs.iter().all(|known| known.name() != desc.name()) {
+            map_descs.push(desc);
+        }
+    }
+    let map_ids: BTreeMap<String, DbMapId> = map_descs
+        .iter()
+        .enumerate()
+        .map(|(idx, desc)| (desc.name().to_owned(), DbMapId::new(idx)))
+        .collect();
+    let metadata_id = DbMapId::new(schema_len);
+    let data_map_ids: Vec<DbMapId> =
+        map_ids.values().copied().filter(|id| *id != metadata_id).collect();
+
+    let latest_version = u32::try_from(migrations.len()).expect("migration count to fit u32");
+    let fingerprint = Sch::fingerprint();
+
+    // Refuse to open the database before any maps it does not have yet are created. Without
+    // a recorded version, there is nothing that could make the database fail to open.
+    let desc = storage_core::types::construct::db_desc(map_descs.into_iter());
+    let backend = backend.open_checked(desc, metadata_id, |metadata| {
+        let version = metadata.get(VERSION_KEY).map(Vec::as_slice);
+        if let Some(version) = decode_metadata::<u32>(version)? {
+            let recorded_fingerprint = metadata.get(FINGERPRINT_KEY).map(Vec::as_slice);
+            let recorded_fingerprint = decode_metadata::<Fingerprint>(recorded_fingerprint)?;
+            check_recorded(
+                version,
+                recorded_fingerprint.as_ref(),
+                latest_version,
+                &fingerprint,
+            )?;
+        }
+        Ok(())
+    })?;
+
+    super::RetryPolicy::default().run(|| {
+        let mut dbtx = backend.transaction_rw(None)?;
+
+        let recorded_version = get_metadata::<u32>(&dbtx, metadata_id, VERSION_KEY)?;
+        let version = match recorded_version {
+            Some(version) => version,
+            None => {
+                let mut has_data = false;
+                for map_id in &data_map_ids {
+                    if dbtx.prefix_iter_keys(*map_id, Vec::new())?.next().transpose()?.is_some() {
+                        has_data = true;
+                        break;
+                    }
+                }
+                if has_data {
+                    0
+                } else {
+                    latest_version
+                }
+            }
+        };
+        let recorded_fingerprint =
+            get_metadata::<Fingerprint>(&dbtx, metadata_id, FINGERPRINT_KEY)?;
+        check_recorded(
+            version,
+            recorded_fingerprint.as_ref(),
+            latest_version,
+            &fingerprint,
+        )?;
+
+        // Leave a database that is up to date untouched, the transaction is just dropped
+        let version_changed = recorded_version != Some(latest_version);
+        let fingerprint_changed = recorded_fingerprint.as_ref() != Some(&fingerprint);
+        if !version_changed && !fingerprint_changed {
+            return Ok(());
+        }
+
+        let mut migration_tx = MigrationTx {
+            dbtx: &mut dbtx,
+            map_ids: &map_ids,
+        };
+        for migration in &migrations[version as usize..] {
+            migration.migrate(&mut migration_tx)?;
+        }
+
+        if version_changed {
+            WriteOps::put(
+                &mut dbtx,
+                metadata_id,
+                VERSION_KEY.to_vec(),
+                latest_version.encode(),
+            )?;
+        }
+        if fingerprint_changed {
+            WriteOps::put(
+                &mut dbtx,
+                metadata_id,
+                FINGERPRINT_KEY.to_vec(),
+                fingerprint.encode(),
+            )?;
+        }
+        backend::TxRw::commit(dbtx)
+    })?;
+
+    Ok(backend)
+}
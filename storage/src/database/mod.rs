@@ -16,9 +16,14 @@
 //! High-level application-agnostic storage interface
 
 mod internal;
+pub mod migration;
 pub mod raw;
+mod retry;
 
-use std::borrow::Cow;
+pub use migration::{Migration, MigrationTx};
+pub use retry::RetryPolicy;
+
+use std::{borrow::Cow, ops::RangeBounds};
 
 use internal::{EntryIterator, TxImpl};
 use utils::shallow_clone::ShallowClone;
@@ -33,6 +38,7 @@ use storage_core::{
 /// The main storage type
 pub struct Storage<B: Backend, Sch> {
     backend: B::Impl,
+    retry_policy: RetryPolicy,
     _schema: core::marker::PhantomData<Sch>,
 }
 
@@ -43,6 +49,7 @@ where
     fn clone(&self) -> Self {
         Self {
             backend: self.backend.clone(),
+            retry_policy: self.retry_policy,
             _schema: Default::default(),
         }
     }
@@ -55,6 +62,7 @@ where
     fn shallow_clone(&self) -> Self {
         Self {
             backend: self.backend.shallow_clone(),
+            retry_policy: self.retry_policy,
             _schema: self._schema.shallow_clone(),
         }
     }
@@ -63,14 +71,37 @@ where
 impl<B: Backend, Sch: Schema> Storage<B, Sch> {
     /// Create new storage with given backend
     pub fn new(backend: B) -> crate::Result<Self> {
-        let backend = backend.open(storage_core::types::construct::db_desc(Sch::desc_iter()))?;
+        Self::new_with_migrations(backend, &[])
+    }
+
+    /// Create new storage with given backend, upgrading the database contents using given
+    /// migrations, see [migration]. The migrations have to be listed in the order they are
+    /// applied and the schema version is the number of migrations.
+    pub fn new_with_migrations(backend: B, migrations: &[&dyn Migration]) -> crate::Result<Self> {
+        let backend = migration::open::<B, Sch>(backend, migrations)?;
+        let retry_policy = RetryPolicy::default();
         let _schema = std::marker::PhantomData;
-        Ok(Self { backend, _schema })
+        Ok(Self {
+            backend,
+            retry_policy,
+            _schema,
+        })
     }
 
     /// Create new storage with given backend and raw dump
     pub fn new_from_dump(backend: B, dump: raw::StorageContents<Sch>) -> crate::Result<Self> {
-        let backend = backend.open(storage_core::types::construct::db_desc(Sch::desc_iter()))?;
+        Self::new_from_dump_with_migrations(backend, dump, &[])
+    }
+
+    /// Create new storage with given backend and raw dump. The dump is taken to be in the
+    /// current format, so the database gets the schema version given by the number of
+    /// migrations, see [Self::new_with_migrations].
+    pub fn new_from_dump_with_migrations(
+        backend: B,
+        dump: raw::StorageContents<Sch>,
+        migrations: &[&dyn Migration],
+    ) -> crate::Result<Self> {
+        let backend = migration::open::<B, Sch>(backend, migrations)?;
         let _schema = std::marker::PhantomData;
         let mut dbtx = backend::BackendImpl::transaction_rw(&backend, None)?;
 
@@ -81,7 +112,18 @@ impl<B: Backend, Sch: Schema> Storage<B, Sch> {
         }
 
         dbtx.commit()?;
-        Ok(Self { backend, _schema })
+        let retry_policy = RetryPolicy::default();
+        Ok(Self {
+            backend,
+            retry_policy,
+            _schema,
+        })
+    }
+
+    /// Set the policy used to retry transactions in [Self::run_ro] and [Self::run_rw]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
     }
 
     /// Start a read-only transaction
@@ -97,6 +139,40 @@ impl<B: Backend, Sch: Schema> Storage<B, Sch> {
         let _schema = std::marker::PhantomData;
         Ok(TransactionRw { dbtx, _schema })
     }
+
+    /// Run given function in a read-only transaction.
+    ///
+    /// If a recoverable error occurs, the transaction is retried according to the retry policy.
+    pub fn run_ro<R>(
+        &self,
+        mut func: impl FnMut(&TransactionRo<'_, B, Sch>) -> crate::Result<R>,
+    ) -> crate::Result<R> {
+        self.retry_policy.run(|| {
+            let dbtx = self.transaction_ro()?;
+            let result = func(&dbtx)?;
+            dbtx.close();
+            Ok(result)
+        })
+    }
+
+    /// Run given function in a read-write transaction.
+    ///
+    /// The transaction is committed if the function succeeds and aborted if it fails. If
+    /// a recoverable error occurs, either in the function or during the commit, the whole
+    /// transaction is retried according to the retry policy. Fatal errors are returned
+    /// immediately.
+    pub fn run_rw<R>(
+        &self,
+        size: Option<usize>,
+        mut func: impl FnMut(&mut TransactionRw<'_, B, Sch>) -> crate::Result<R>,
+    ) -> crate::Result<R> {
+        self.retry_policy.run(|| {
+            let mut dbtx = self.transaction_rw(size)?;
+            let result = func(&mut dbtx)?;
+            dbtx.commit()?;
+            Ok(result)
+        })
+    }
 }
 
 pub trait MakeMapRef<'tx, B: Backend, Sch: Schema>: TxImpl + Sized {
@@ -157,6 +233,22 @@ impl<'tx, B: Backend, Sch: Schema> TransactionRw<'tx, B, Sch> {
         MapMut::new(&mut self.dbtx, <Sch as schema::HasDbMap<DbMap, I>>::INDEX)
     }
 
+    /// Create a savepoint. Changes made after this point can be undone using
+    /// [Self::rollback_to] without aborting the whole transaction.
+    pub fn savepoint(&mut self) -> crate::Result<()> {
+        backend::TxRw::savepoint(&mut self.dbtx)
+    }
+
+    /// Discard the changes made since the most recent savepoint and remove the savepoint
+    pub fn rollback_to(&mut self) -> crate::Result<()> {
+        backend::TxRw::rollback_to(&mut self.dbtx)
+    }
+
+    /// Remove the most recent savepoint, keeping the changes made since it was created
+    pub fn release(&mut self) -> crate::Result<()> {
+        backend::TxRw::release(&mut self.dbtx)
+    }
+
     /// Commit the transaction
     pub fn commit(self) -> crate::Result<()> {
         backend::TxRw::commit(self.dbtx)
@@ -200,7 +292,10 @@ where
     }
 
     /// Iterator over entries with key starting with given prefix
-    pub fn prefix_iter<Pfx>(&self, prefix: &Pfx) -> crate::Result<impl '_ + EntryIterator<DbMap>>
+    pub fn prefix_iter<Pfx>(
+        &self,
+        prefix: &Pfx,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>>
     where
         Pfx: Encode,
         DbMap::Key: HasPrefix<Pfx>,
@@ -208,11 +303,23 @@ where
         internal::prefix_iter(self.dbtx, self.map_id, prefix.encode())
     }
 
+    /// Iterator over entries with key starting with given prefix, in descending key order
+    pub fn prefix_iter_rev<Pfx>(
+        &self,
+        prefix: &Pfx,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>>
+    where
+        Pfx: Encode,
+        DbMap::Key: HasPrefix<Pfx>,
+    {
+        internal::prefix_iter_rev(self.dbtx, self.map_id, prefix.encode())
+    }
+
     /// Iterator over entries with key starting with given prefix
     pub fn prefix_iter_keys<Pfx>(
         &self,
         prefix: &Pfx,
-    ) -> crate::Result<impl '_ + Iterator<Item = DbMap::Key>>
+    ) -> crate::Result<impl '_ + Iterator<Item = crate::Result<DbMap::Key>>>
     where
         Pfx: Encode,
         DbMap::Key: HasPrefix<Pfx>,
@@ -220,16 +327,43 @@ where
         internal::prefix_iter_keys::<DbMap, _>(self.dbtx, self.map_id, prefix.encode())
     }
 
+    /// Iterator over entries with key in given range.
+    ///
+    /// Note the range is taken over the encoded keys, which may order differently from the keys
+    /// themselves.
+    pub fn range_iter(
+        &self,
+        range: impl RangeBounds<DbMap::Key>,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+        let start = internal::encode_bound(range.start_bound());
+        let end = internal::encode_bound(range.end_bound());
+        internal::range_iter(self.dbtx, self.map_id, start, end)
+    }
+
+    /// Iterator over entries with key in given range, in descending key order.
+    ///
+    /// Note the range is taken over the encoded keys, which may order differently from the keys
+    /// themselves.
+    pub fn range_iter_rev(
+        &self,
+        range: impl RangeBounds<DbMap::Key>,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+        let start = internal::encode_bound(range.start_bound());
+        let end = internal::encode_bound(range.end_bound());
+        internal::range_iter_rev(self.dbtx, self.map_id, start, end)
+    }
+
     /// Iterator over decoded entries with key starting with given prefix
     pub fn prefix_iter_decoded<Pfx>(
         &self,
         prefix: &Pfx,
-    ) -> crate::Result<impl '_ + Iterator<Item = (DbMap::Key, DbMap::Value)>>
+    ) -> crate::Result<impl '_ + Iterator<Item = crate::Result<(DbMap::Key, DbMap::Value)>>>
     where
         Pfx: Encode,
         DbMap::Key: HasPrefix<Pfx>,
     {
-        self.prefix_iter(prefix).map(|item| item.map(|(k, v)| (k, v.decode())))
+        let iter = self.prefix_iter(prefix)?;
+        Ok(iter.map(|item| item.map(|(k, v)| (k, v.decode()))))
     }
 }
 
@@ -265,13 +399,54 @@ where
     }
 
     /// Iterator over entries with key starting with given prefix
-    pub fn prefix_iter<Pfx>(&self, prefix: &Pfx) -> crate::Result<impl '_ + EntryIterator<DbMap>>
+    pub fn prefix_iter<Pfx>(
+        &self,
+        prefix: &Pfx,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>>
     where
         Pfx: Encode,
         DbMap::Key: HasPrefix<Pfx>,
     {
         internal::prefix_iter(self.dbtx, self.map_id, prefix.encode())
     }
+
+    /// Iterator over entries with key starting with given prefix, in descending key order
+    pub fn prefix_iter_rev<Pfx>(
+        &self,
+        prefix: &Pfx,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>>
+    where
+        Pfx: Encode,
+        DbMap::Key: HasPrefix<Pfx>,
+    {
+        internal::prefix_iter_rev(self.dbtx, self.map_id, prefix.encode())
+    }
+
+    /// Iterator over entries with key in given range.
+    ///
+    /// Note the range is taken over the encoded keys, which may order differently from the keys
+    /// themselves.
+    pub fn range_iter(
+        &self,
+        range: impl RangeBounds<DbMap::Key>,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+        let start = internal::encode_bound(range.start_bound());
+        let end = internal::encode_bound(range.end_bound());
+        internal::range_iter(self.dbtx, self.map_id, start, end)
+    }
+
+    /// Iterator over entries with key in given range, in descending key order.
+    ///
+    /// Note the range is taken over the encoded keys, which may order differently from the keys
+    /// themselves.
+    pub fn range_iter_rev(
+        &self,
+        range: impl RangeBounds<DbMap::Key>,
+    ) -> crate::Result<impl '_ + EntryIterator<'_, DbMap>> {
+        let start = internal::encode_bound(range.start_bound());
+        let end = internal::encode_bound(range.end_bound());
+        internal::range_iter_rev(self.dbtx, self.map_id, start, end)
+    }
 }
 
 impl<Tx: TxImpl, DbMap: schema::DbMap> MapMut<'_, Tx, DbMap>
@@ -291,6 +466,66 @@ where
     pub fn del<K: EncodeLike<DbMap::Key>>(&mut self, key: K) -> crate::Result<()> {
         key.using_encoded(|key| backend::WriteOps::del(self.dbtx, self.map_id, key))
     }
+
+    /// Remove all values with key starting with given prefix.
+    pub fn del_prefix<Pfx>(&mut self, prefix: &Pfx) -> crate::Result<()>
+    where
+        Pfx: Encode,
+        DbMap::Key: HasPrefix<Pfx>,
+    {
+        backend::WriteOps::del_prefix(self.dbtx, self.map_id, prefix.encode())
+    }
+
+    /// Remove all values with key in given range.
+    ///
+    /// Note the range is taken over the encoded keys, which may order differently from the keys
+    /// themselves.
+    pub fn del_range(&mut self, range: impl RangeBounds<DbMap::Key>) -> crate::Result<()> {
+        let start = internal::encode_bound(range.start_bound());
+        let end = internal::encode_bound(range.end_bound());
+        backend::WriteOps::del_range(self.dbtx, self.map_id, start, end)
+    }
+
+    /// Remove all values in the map.
+    pub fn clear(&mut self) -> crate::Result<()> {
+        backend::WriteOps::clear(self.dbtx, self.map_id)
+    }
+
+    /// Put a new value associated with given key only if there is no value associated with it
+    /// yet. Returns whether the value has been put.
+    pub fn put_if_absent<K: EncodeLike<DbMap::Key>, V: EncodeLike<DbMap::Value>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> crate::Result<bool> {
+        backend::WriteOps::put_if_absent(self.dbtx, self.map_id, key.encode(), value.encode())
+    }
+
+    /// Replace the value associated with given key only if the current value equals `expected`.
+    /// Returns whether the value has been replaced.
+    ///
+    /// The values are compared in their encoded form. A `None` in `expected` stands for no value
+    /// being associated with the key. A `None` in `new` removes the value.
+    pub fn compare_and_swap<K, E, V>(
+        &mut self,
+        key: K,
+        expected: Option<E>,
+        new: Option<V>,
+    ) -> crate::Result<bool>
+    where
+        K: EncodeLike<DbMap::Key>,
+        E: EncodeLike<DbMap::Value>,
+        V: EncodeLike<DbMap::Value>,
+    {
+        let expected = expected.map(|val| val.encode());
+        backend::WriteOps::compare_and_swap(
+            self.dbtx,
+            self.map_id,
+            key.encode(),
+            expected.as_deref(),
+            new.map(|val| val.encode()),
+        )
+    }
 }
 
 /// Marker asserting type `Pfx` is an encoding prefix of `Self`
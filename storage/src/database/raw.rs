@@ -136,7 +136,10 @@ pub fn dump_storage<B: Backend, Sch: Schema>(
         .map(|(idx, _dbinfo)| {
             let idx = storage_core::DbMapId::new(idx);
             let items = dbtx.dbtx.prefix_iter(idx, Vec::new())?;
-            Ok((DbMapId::from_idx_unchecked(idx), items.collect()))
+            let items = items
+                .map(|item| item.map(|(k, v)| (k.into_owned(), v.into_owned())))
+                .collect::<crate::Result<_>>()?;
+            Ok((DbMapId::from_idx_unchecked(idx), items))
         })
         .collect::<crate::Result<StorageContents<Sch>>>()
 }
@@ -0,0 +1,92 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retrying transactions that failed with a recoverable error
+
+use std::time::Duration;
+
+/// Policy specifying how transactions failing with a recoverable error are retried.
+///
+/// The delay between attempts starts at the initial backoff and doubles after each failed attempt
+/// up to the maximum backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy making at most given number of attempts, with the default backoff settings.
+    ///
+    /// Zero attempts is treated as one, a transaction is always attempted at least once.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: std::cmp::max(max_attempts, 1),
+            ..Self::default()
+        }
+    }
+
+    /// Policy that does not retry at all
+    pub fn no_retry() -> Self {
+        Self::new(1)
+    }
+
+    /// Set the delay before the first retry
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the upper limit on the delay between attempts
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Maximum number of attempts
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Run given function, retrying it while it fails with a recoverable error and attempts
+    /// remain. Fatal errors and errors from the last attempt are returned as is.
+    pub(crate) fn run<R>(&self, mut attempt: impl FnMut() -> crate::Result<R>) -> crate::Result<R> {
+        let mut backoff = std::cmp::min(self.initial_backoff, self.max_backoff);
+        let mut attempts_left = self.max_attempts;
+        loop {
+            attempts_left -= 1;
+            match attempt() {
+                Err(crate::Error::Recoverable(_)) if attempts_left > 0 => {
+                    if !backoff.is_zero() {
+                        std::thread::sleep(backoff);
+                    }
+                    backoff = std::cmp::min(backoff.saturating_mul(2), self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
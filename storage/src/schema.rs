@@ -15,7 +15,9 @@
 
 //! Describe the database schema at type level
 
-pub use storage_core::{DbMapDesc, DbMapId};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub use storage_core::{DbMapDesc, DbMapId, KeyOrder};
 
 /// Describes single key-value map
 pub trait DbMap: 'static {
@@ -25,6 +27,19 @@ pub trait DbMap: 'static {
     /// Expected size of values in the map. May be used for storage optimization.
     const SIZE_HINT: core::ops::Range<usize> = 0..usize::MAX;
 
+    /// How the keys are ordered, see [KeyOrder]. Backends that only support the lexicographic
+    /// order refuse to open the database otherwise.
+    const KEY_ORDER: KeyOrder = KeyOrder::Lexicographic;
+
+    /// Whether each key can hold multiple values, see [DbMapDesc::with_duplicate_values].
+    /// Backends that do not support it refuse to open the database.
+    const DUPLICATE_VALUES: bool = false;
+
+    /// Names of the key and value types recorded in the schema fingerprint, see [Fingerprint].
+    /// Maps without them are only identified by name. Maps declared using [crate::decl_schema]
+    /// get the types as written in the declaration.
+    const FINGERPRINT: Option<(&'static str, &'static str)> = None;
+
     /// Type of keys in the map
     type Key: serialization::Codec;
 
@@ -32,10 +47,74 @@ pub trait DbMap: 'static {
     type Value: serialization::Codec;
 }
 
+/// Fingerprint of a schema, made up of the name of each map along with the names of its key and
+/// value types, if given by [DbMap::FINGERPRINT]. It is recorded in the database to detect it
+/// being opened with a schema that interprets the stored data differently.
+///
+/// Only the type names are compared, not the types themselves nor their encoding. Two distinct
+/// types with the same name, e.g. from different modules, are indistinguishable, as is a type
+/// whose encoding changes while its name stays the same. Conversely, renaming a type or
+/// referring to it by a different path makes the fingerprint differ even though the data is
+/// still valid.
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct Fingerprint(BTreeMap<String, Option<(String, String)>>);
+
+/// Describe the key and value types of a map in a fingerprint
+fn describe_types(types: &Option<(String, String)>) -> String {
+    match types {
+        Some((key, value)) => format!("{key} -> {value}"),
+        None => "untyped".to_owned(),
+    }
+}
+
+impl Fingerprint {
+    /// Describe the maps that differ between this fingerprint, as recorded in the database, and
+    /// the one of the schema, one line per map
+    pub fn diff(&self, schema: &Self) -> Vec<String> {
+        let names: BTreeSet<&String> = self.0.keys().chain(schema.0.keys()).collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let (recorded, current) = (self.0.get(name), schema.0.get(name));
+                (recorded != current).then(|| {
+                    let recorded = match recorded {
+                        Some(types) => format!("recorded {}", describe_types(types)),
+                        None => "not recorded".to_owned(),
+                    };
+                    let current = match current {
+                        Some(types) => format!("{} in schema", describe_types(types)),
+                        None => "missing in schema".to_owned(),
+                    };
+                    format!("{name}: {recorded}, {current}")
+                })
+            })
+            .collect()
+    }
+}
+
+impl serialization::Encode for Fingerprint {
+    fn size_hint(&self) -> usize {
+        self.0.size_hint()
+    }
+
+    fn encode_to<O: serialization::Output + ?Sized>(&self, dest: &mut O) {
+        self.0.encode_to(dest)
+    }
+}
+
+impl serialization::Decode for Fingerprint {
+    fn decode<I: serialization::Input>(input: &mut I) -> Result<Self, serialization::Error> {
+        BTreeMap::decode(input).map(Self)
+    }
+}
+
 /// What constitutes a valid database schema
 pub trait Schema: internal::Sealed + 'static {
     type DescIter: Iterator<Item = DbMapDesc>;
     fn desc_iter() -> Self::DescIter;
+
+    /// Fingerprint of the schema, see [Fingerprint]
+    fn fingerprint() -> Fingerprint;
 }
 
 impl Schema for () {
@@ -43,14 +122,31 @@ impl Schema for () {
     fn desc_iter() -> Self::DescIter {
         std::iter::empty()
     }
+
+    fn fingerprint() -> Fingerprint {
+        Fingerprint::default()
+    }
 }
 
 impl<M: DbMap, Rest: Schema> Schema for (M, Rest) {
     type DescIter = std::iter::Chain<std::iter::Once<DbMapDesc>, Rest::DescIter>;
     fn desc_iter() -> Self::DescIter {
-        let map_desc = DbMapDesc::new_with_details(M::NAME, M::SIZE_HINT);
+        let map_desc =
+            DbMapDesc::new_with_details(M::NAME, M::SIZE_HINT).with_key_order(M::KEY_ORDER);
+        let map_desc = if M::DUPLICATE_VALUES {
+            map_desc.with_duplicate_values()
+        } else {
+            map_desc
+        };
         std::iter::once(map_desc).chain(Rest::desc_iter())
     }
+
+    fn fingerprint() -> Fingerprint {
+        let mut fingerprint = Rest::fingerprint();
+        let types = M::FINGERPRINT.map(|(key, value)| (key.to_owned(), value.to_owned()));
+        fingerprint.0.insert(M::NAME.to_owned(), types);
+        fingerprint
+    }
 }
 
 /// Require a schema to contain given map (identified by a type tag)
@@ -87,6 +183,8 @@ macro_rules! decl_schema {
             $map_vis struct $name;
             impl $crate::schema::DbMap for $name {
                 const NAME: &'static str = stringify!($name);
+                const FINGERPRINT: Option<(&'static str, &'static str)> =
+                    Some((stringify!($key), stringify!($val)));
                 type Key = $key;
                 type Value = $val;
             }
@@ -117,6 +215,43 @@ mod test {
         }
     }
 
+    mod other {
+        decl_schema! {
+            pub OtherSchema {
+                pub DBIdx0: Map<u8, u16>,
+                pub DBIdx1: Map<u16, u32>,
+                pub DBIdx3: Map<u8, Vec<u8>>,
+            }
+        }
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(MySchema::fingerprint(), MySchema::fingerprint());
+        assert!(MySchema::fingerprint().diff(&MySchema::fingerprint()).is_empty());
+
+        let diff = MySchema::fingerprint().diff(&other::OtherSchema::fingerprint());
+        let expected = [
+            "DBIdx1: recorded u8 -> u32, u16 -> u32 in schema",
+            "DBIdx2: recorded u8 -> u64, missing in schema",
+            "DBIdx3: not recorded, u8 -> Vec<u8> in schema",
+        ];
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn fingerprint_opt_in() {
+        struct Untyped;
+        impl DbMap for Untyped {
+            const NAME: &'static str = "DBIdx0";
+            type Key = u8;
+            type Value = u16;
+        }
+
+        let diff = <(Untyped, ())>::fingerprint().diff(&<(DBIdx0, ())>::fingerprint());
+        assert_eq!(diff, ["DBIdx0: recorded untyped, u8 -> u16 in schema"]);
+    }
+
     #[test]
     fn schema() {
         // Check calculated column indices
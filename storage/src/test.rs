@@ -14,6 +14,7 @@
 // limitations under the License.
 
 use super::*;
+use schema::{DbMap, KeyOrder};
 use storage_core::Data;
 
 decl_schema! {
@@ -22,6 +23,30 @@ decl_schema! {
     }
 }
 
+struct ReverseMap;
+
+impl DbMap for ReverseMap {
+    const NAME: &'static str = "ReverseMap";
+    const KEY_ORDER: KeyOrder = KeyOrder::Reverse;
+    type Key = Data;
+    type Value = Data;
+}
+
+#[test]
+fn unsupported_map_layout() {
+    let result = Storage::<_, (ReverseMap, ())>::new(inmemory::InMemory::new());
+    assert_eq!(
+        result.err(),
+        Some(
+            storage_core::error::Fatal::SchemaMismatch(
+                "Map ReverseMap needs a key order or duplicate values not supported by the backend"
+                    .into()
+            )
+            .into()
+        )
+    );
+}
+
 #[test]
 fn empty_ro() {
     utils::concurrency::model(|| {
@@ -40,6 +65,79 @@ fn empty_rw() {
     });
 }
 
+#[test]
+fn savepoint_rollback() {
+    utils::concurrency::model(|| {
+        let store = Storage::<_, Schema>::new(inmemory::InMemory::new()).unwrap();
+        let (key0, key1) = (b"foo".to_vec(), b"bar".to_vec());
+
+        let mut tx = store.transaction_rw(None).unwrap();
+        tx.get_mut::<Map1, _>().put(&key0, b"0".to_vec()).unwrap();
+
+        // Tentatively apply a batch of writes, then roll back just that batch
+        tx.savepoint().unwrap();
+        tx.get_mut::<Map1, _>().put(&key0, b"1".to_vec()).unwrap();
+        tx.get_mut::<Map1, _>().put(&key1, b"1".to_vec()).unwrap();
+        tx.rollback_to().unwrap();
+        tx.commit().unwrap();
+
+        let tx = store.transaction_ro().unwrap();
+        let map = tx.get::<Map1, _>();
+        assert_eq!(
+            map.get(&key0).unwrap().map(|v| v.decode()),
+            Some(b"0".to_vec())
+        );
+        assert_eq!(map.get(&key1), Ok(None));
+    });
+}
+
+#[test]
+fn run_with_retries() {
+    use crate::error::{Fatal, Recoverable};
+
+    utils::concurrency::model(|| {
+        let policy = RetryPolicy::new(3).with_initial_backoff(std::time::Duration::ZERO);
+        let store = Storage::<_, Schema>::new(inmemory::InMemory::new())
+            .unwrap()
+            .with_retry_policy(policy);
+        let key = b"foo".to_vec();
+
+        // Fail twice with a recoverable error, then succeed
+        let mut attempts = 0u8;
+        let result = store.run_rw(None, |tx| {
+            attempts += 1;
+            tx.get_mut::<Map1, _>().put(&key, vec![attempts])?;
+            utils::ensure!(attempts == 3, Recoverable::TransactionFailed);
+            Ok(attempts)
+        });
+        assert_eq!(result, Ok(3));
+
+        // Keep failing with a recoverable error until the attempts run out
+        attempts = 0;
+        let result = store.run_rw(None, |tx| {
+            attempts += 1;
+            tx.get_mut::<Map1, _>().put(&key, vec![0])?;
+            Err::<(), _>(Recoverable::TemporarilyUnavailable.into())
+        });
+        assert_eq!(result, Err(Recoverable::TemporarilyUnavailable.into()));
+        assert_eq!(attempts, 3);
+
+        // Fatal errors are not retried
+        attempts = 0;
+        let result = store.run_rw(None, |tx| {
+            attempts += 1;
+            tx.get_mut::<Map1, _>().put(&key, vec![0])?;
+            Err::<(), _>(Fatal::DatabaseCorrupted.into())
+        });
+        assert_eq!(result, Err(Fatal::DatabaseCorrupted.into()));
+        assert_eq!(attempts, 1);
+
+        // Only the successful transaction has been committed
+        let val = store.run_ro(|tx| Ok(tx.get::<Map1, _>().get(&key)?.map(|v| v.decode())));
+        assert_eq!(val, Ok(Some(vec![3])));
+    });
+}
+
 decl_schema! {
     // Schema with a compound key
     Compound {
@@ -75,6 +173,7 @@ fn prefix_iteration() {
             .get::<Map2, _>()
             .prefix_iter(&("foo".into(),))
             .unwrap()
+            .map(Result::unwrap)
             .map(|((_, k), v)| (k, v.decode()))
             .collect();
         let expected = vec![(1, 1), (2, 2), (12, 0)];
@@ -87,6 +186,7 @@ fn prefix_iteration() {
             .get::<Map2, _>()
             .prefix_iter_decoded(&("foo".into(),))
             .unwrap()
+            .map(Result::unwrap)
             .map(|((_, k), v)| (k, v))
             .collect();
         assert_eq!(items, expected);
@@ -103,6 +203,7 @@ fn prefix_iteration() {
             .get::<Map2, _>()
             .prefix_iter(&())
             .unwrap()
+            .map(Result::unwrap)
             .map(|(k, v)| (k, v.decode()))
             .collect();
         assert_eq!(items, test_values_sorted);
@@ -114,9 +215,151 @@ fn prefix_iteration() {
             .get::<Map2, _>()
             .prefix_iter_decoded(&())
             .unwrap()
+            .map(Result::unwrap)
             .map(|(k, v)| (k, v))
             .collect();
         assert_eq!(items, test_values_sorted);
         dbtx.close();
     });
 }
+
+/// Storage with a few entries in the map with compound keys, under several prefixes
+fn populated_store() -> Storage<inmemory::InMemory, Compound> {
+    let store = Storage::<_, Compound>::new(inmemory::InMemory::new()).unwrap();
+
+    let test_values = [
+        ((String::from("foo"), 12), 0),
+        ((String::from("foo"), 1), 1),
+        ((String::from("bar"), 42), 3),
+        ((String::from("bar"), 43), 4),
+        ((String::from("hello"), 1337), 6),
+    ];
+
+    // Populate the database
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    let mut map = dbtx.get_mut::<Map2, _>();
+    for (key, val) in &test_values {
+        map.put(key, val).unwrap();
+    }
+    dbtx.commit().unwrap();
+    store
+}
+
+#[test]
+fn range_iteration() {
+    utils::concurrency::model(|| {
+        let store = populated_store();
+
+        let start = (String::from("bar"), 43);
+        let end = (String::from("foo"), 12);
+
+        // Iterate over a range of committed entries
+        let dbtx = store.transaction_ro().unwrap();
+        let items: Vec<_> = dbtx
+            .get::<Map2, _>()
+            .range_iter(&start..&end)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|((s, k), v)| (s, k, v.decode()))
+            .collect();
+        let expected = vec![(String::from("bar"), 43, 4), (String::from("foo"), 1, 1)];
+        assert_eq!(items, expected);
+        dbtx.close();
+
+        // Iterate over a range including uncommitted changes
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        let mut map = dbtx.get_mut::<Map2, _>();
+        map.put((String::from("foo"), 2), 2).unwrap();
+        map.del(&start).unwrap();
+        let items: Vec<_> = map
+            .range_iter(&start..=&end)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|((s, k), v)| (s, k, v.decode()))
+            .collect();
+        let expected = vec![
+            (String::from("foo"), 1, 1),
+            (String::from("foo"), 2, 2),
+            (String::from("foo"), 12, 0),
+        ];
+        assert_eq!(items, expected);
+        dbtx.abort();
+    });
+}
+
+#[test]
+fn reverse_iteration() {
+    utils::concurrency::model(|| {
+        let store = populated_store();
+
+        // Iterate over committed entries with given prefix, last one first
+        let dbtx = store.transaction_ro().unwrap();
+        let items: Vec<_> = dbtx
+            .get::<Map2, _>()
+            .prefix_iter_rev(&(String::from("foo"),))
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|((s, k), v)| (s, k, v.decode()))
+            .collect();
+        let expected = vec![(String::from("foo"), 12, 0), (String::from("foo"), 1, 1)];
+        assert_eq!(items, expected);
+        dbtx.close();
+
+        // Iterate backwards over a range including uncommitted changes
+        let end = (String::from("foo"), 12);
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        let mut map = dbtx.get_mut::<Map2, _>();
+        map.put((String::from("foo"), 2), 2).unwrap();
+        map.del((String::from("foo"), 1)).unwrap();
+        let items: Vec<_> = map
+            .range_iter_rev(..&end)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|((s, k), v)| (s, k, v.decode()))
+            .collect();
+        let expected = vec![
+            (String::from("foo"), 2, 2),
+            (String::from("bar"), 43, 4),
+            (String::from("bar"), 42, 3),
+        ];
+        assert_eq!(items, expected);
+        dbtx.abort();
+    });
+}
+
+#[test]
+fn prefix_deletion() {
+    utils::concurrency::model(|| {
+        let store = populated_store();
+
+        // Delete everything under the "foo" prefix
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        dbtx.get_mut::<Map2, _>().del_prefix(&(String::from("foo"),)).unwrap();
+        dbtx.commit().unwrap();
+
+        let dbtx = store.transaction_ro().unwrap();
+        let items: Vec<_> = dbtx
+            .get::<Map2, _>()
+            .prefix_iter_decoded(&())
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|((s, k), v)| (s, k, v))
+            .collect();
+        let expected = vec![
+            (String::from("bar"), 42, 3),
+            (String::from("bar"), 43, 4),
+            (String::from("hello"), 1337, 6),
+        ];
+        assert_eq!(items, expected);
+        dbtx.close();
+
+        // Clear the whole map
+        let mut dbtx = store.transaction_rw(None).unwrap();
+        dbtx.get_mut::<Map2, _>().clear().unwrap();
+        dbtx.commit().unwrap();
+
+        let dbtx = store.transaction_ro().unwrap();
+        assert_eq!(dbtx.get::<Map2, _>().prefix_iter(&()).unwrap().count(), 0);
+        dbtx.close();
+    });
+}
@@ -0,0 +1,292 @@
+// Copyright (c) 2023 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://github.com/mintlayer/mintlayer-core/blob/master/LICENSE
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for schema versioning and migrations
+
+use std::path::Path;
+
+use serialization::Encode;
+use storage::{error::Fatal, Backend, MakeMapRef, Migration, MigrationTx, Storage};
+use storage_core::{
+    backend::{BackendImpl, TxRw, WriteOps},
+    DbMapDesc, DbMapId,
+};
+use storage_sqlite::Sqlite;
+
+mod schema_v0 {
+    storage::decl_schema! {
+        pub Schema {
+            pub Old: Map<u32, u16>,
+            pub Kept: Map<u32, u32>,
+        }
+    }
+}
+
+mod schema_v0_wide {
+    storage::decl_schema! {
+        pub Schema {
+            pub Old: Map<u32, u64>,
+            pub Kept: Map<u32, u32>,
+        }
+    }
+}
+
+mod schema_v1 {
+    storage::decl_schema! {
+        pub Schema {
+            pub New: Map<u32, u64>,
+            pub Kept: Map<u32, u32>,
+        }
+    }
+}
+
+/// Moves the `Old` map to `New`, widening the values
+struct MoveAndWiden;
+
+impl Migration for MoveAndWiden {
+    fn legacy_maps(&self) -> Vec<DbMapDesc> {
+        vec![DbMapDesc::new("Old")]
+    }
+
+    fn migrate(&self, tx: &mut MigrationTx<'_>) -> storage::Result<()> {
+        tx.move_entries("Old", "New")?;
+        tx.reencode::<u16, u64>("New", u64::from)
+    }
+}
+
+/// Clears the `Old` map, then fails
+struct ClearAndFail;
+
+impl Migration for ClearAndFail {
+    fn legacy_maps(&self) -> Vec<DbMapDesc> {
+        vec![DbMapDesc::new("Old")]
+    }
+
+    fn migrate(&self, tx: &mut MigrationTx<'_>) -> storage::Result<()> {
+        tx.clear_map("Old")?;
+        Err(Fatal::InternalError("Migration failed".into()).into())
+    }
+}
+
+fn create_v0(path: &Path) {
+    let store = Storage::<_, schema_v0::Schema>::new(Sqlite::new(path)).unwrap();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    dbtx.get_mut::<schema_v0::Old, _>().put(1, 100).unwrap();
+    dbtx.get_mut::<schema_v0::Old, _>().put(2, 200).unwrap();
+    dbtx.get_mut::<schema_v0::Kept, _>().put(3, 300).unwrap();
+    dbtx.commit().unwrap();
+}
+
+fn open_v1(path: &Path, migrations: &[&dyn Migration]) -> Storage<Sqlite, schema_v1::Schema> {
+    Storage::new_with_migrations(Sqlite::new(path), migrations).unwrap()
+}
+
+/// Names of the tables in the SQLite database, including the ones holding the maps
+fn table_names(path: &Path) -> Vec<String> {
+    let connection = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+        .unwrap();
+    let names = stmt.query_map([], |row| row.get(0)).unwrap();
+    names.map(Result::unwrap).collect()
+}
+
+fn v1_contents(store: &Storage<Sqlite, schema_v1::Schema>) -> (Vec<(u32, u64)>, Option<u32>) {
+    let dbtx = store.transaction_ro().unwrap();
+    let new_map = dbtx.get::<schema_v1::New, _>();
+    let new = new_map.prefix_iter_decoded(&()).unwrap().map(Result::unwrap).collect();
+    let kept = dbtx.get::<schema_v1::Kept, _>().get(3).unwrap().map(|v| v.decode());
+    (new, kept)
+}
+
+#[test]
+fn migration_applied() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("applied").as_ref().join("database.sqlite");
+    create_v0(&path);
+
+    let store = open_v1(&path, &[&MoveAndWiden]);
+    assert_eq!(v1_contents(&store), (vec![(1, 100), (2, 200)], Some(300)));
+    drop(store);
+
+    // The migration does not run again
+    let store = open_v1(&path, &[&MoveAndWiden]);
+    assert_eq!(v1_contents(&store), (vec![(1, 100), (2, 200)], Some(300)));
+    drop(store);
+
+    // Older code refuses to open the database, leaving it as it is
+    let tables = table_names(&path);
+    let result = Storage::<_, schema_v0::Schema>::new(Sqlite::new(&path));
+    assert_eq!(result.err(), Some(Fatal::SchemaVersionTooNew(1, 0).into()));
+    assert_eq!(table_names(&path), tables);
+
+    test_root.delete();
+}
+
+#[test]
+fn large_map_migrated() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("large").as_ref().join("database.sqlite");
+
+    // Enough entries for the migration to go through the map in several batches
+    let store = Storage::<_, schema_v0::Schema>::new(Sqlite::new(&path)).unwrap();
+    let mut dbtx = store.transaction_rw(None).unwrap();
+    for key in 0..2500u32 {
+        dbtx.get_mut::<schema_v0::Old, _>().put(key, key as u16).unwrap();
+    }
+    dbtx.commit().unwrap();
+    drop(store);
+
+    let store = open_v1(&path, &[&MoveAndWiden]);
+    let (mut new, kept) = v1_contents(&store);
+    new.sort();
+    assert_eq!(
+        new,
+        (0..2500u32).map(|key| (key, key as u64)).collect::<Vec<_>>()
+    );
+    assert_eq!(kept, None);
+    drop(store);
+
+    test_root.delete();
+}
+
+#[test]
+fn restored_dump_not_migrated() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let source_path = test_root.fresh_test_dir("dump_source").as_ref().join("database.sqlite");
+    let path = test_root.fresh_test_dir("dump_target").as_ref().join("database.sqlite");
+    create_v0(&source_path);
+
+    let store = open_v1(&source_path, &[&MoveAndWiden]);
+    let dump = store.transaction_ro().unwrap().dump_raw().unwrap();
+    drop(store);
+
+    // The dump is already in the current format, the migration must not run on it again
+    let store = Storage::<_, schema_v1::Schema>::new_from_dump_with_migrations(
+        Sqlite::new(&path),
+        dump,
+        &[&MoveAndWiden],
+    )
+    .unwrap();
+    drop(store);
+    let store = open_v1(&path, &[&MoveAndWiden]);
+    assert_eq!(v1_contents(&store), (vec![(1, 100), (2, 200)], Some(300)));
+    drop(store);
+
+    test_root.delete();
+}
+
+#[test]
+fn unversioned_database_migrated() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("unversioned").as_ref().join("database.sqlite");
+
+    // Write the data directly through the backend, with no version recorded
+    let desc = [DbMapDesc::new("Old"), DbMapDesc::new("Kept")];
+    let backend = Sqlite::new(&path);
+    let backend = backend.open(storage_core::types::construct::db_desc(desc.into_iter())).unwrap();
+    let mut dbtx = backend.transaction_rw(None).unwrap();
+    dbtx.put(DbMapId::new(0), 1u32.encode(), 100u16.encode()).unwrap();
+    dbtx.put(DbMapId::new(1), 3u32.encode(), 300u32.encode()).unwrap();
+    dbtx.commit().unwrap();
+    drop(backend);
+
+    let store = open_v1(&path, &[&MoveAndWiden]);
+    assert_eq!(v1_contents(&store), (vec![(1, 100)], Some(300)));
+    drop(store);
+
+    test_root.delete();
+}
+
+#[test]
+fn failed_migration_rolled_back() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("failed").as_ref().join("database.sqlite");
+    create_v0(&path);
+
+    let result =
+        Storage::<_, schema_v1::Schema>::new_with_migrations(Sqlite::new(&path), &[&ClearAndFail]);
+    assert_eq!(
+        result.err(),
+        Some(Fatal::InternalError("Migration failed".into()).into())
+    );
+
+    // The original contents are intact
+    let store = Storage::<_, schema_v0::Schema>::new(Sqlite::new(&path)).unwrap();
+    let dbtx = store.transaction_ro().unwrap();
+    let val = dbtx.get::<schema_v0::Old, _>().get(1).unwrap().map(|v| v.decode());
+    assert_eq!(val, Some(100));
+    dbtx.close();
+    drop(store);
+
+    test_root.delete();
+}
+
+#[test]
+fn fresh_database_not_migrated() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("fresh").as_ref().join("database.sqlite");
+
+    // The failing migration is only needed for databases created by older code
+    let store = open_v1(&path, &[&ClearAndFail]);
+    assert_eq!(v1_contents(&store), (vec![], None));
+    drop(store);
+
+    test_root.delete();
+}
+
+#[test]
+fn schema_change_without_migration() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("no_migration").as_ref().join("database.sqlite");
+    create_v0(&path);
+    let tables = table_names(&path);
+
+    let result = Storage::<_, schema_v1::Schema>::new(Sqlite::new(&path));
+    let expected = Fatal::SchemaMismatch(
+        "Maps changed: New: not recorded, u32 -> u64 in schema; \
+         Old: recorded u32 -> u16, missing in schema"
+            .into(),
+    );
+    assert_eq!(result.err(), Some(expected.into()));
+
+    // The map of the new schema has not been created
+    assert_eq!(table_names(&path), tables);
+
+    test_root.delete();
+}
+
+#[test]
+fn changed_types_detected() {
+    let test_root = test_utils::test_root!("migration-tests").unwrap();
+    let path = test_root.fresh_test_dir("changed_types").as_ref().join("database.sqlite");
+    create_v0(&path);
+
+    // Same map names, different value type
+    let result = Storage::<_, schema_v0_wide::Schema>::new(Sqlite::new(&path));
+    let expected = Fatal::SchemaMismatch(
+        "Maps changed: Old: recorded u32 -> u16, u32 -> u64 in schema".into(),
+    );
+    assert_eq!(result.err(), Some(expected.into()));
+
+    // The original schema still opens the database. This relies on the fingerprint only
+    // recording type names: the types of schema_v0 and the ones that wrote the data are the same
+    // here, but any types named u32 and u16 would be accepted just as well.
+    let store = Storage::<_, schema_v0::Schema>::new(Sqlite::new(&path));
+    assert!(store.is_ok());
+    drop(store);
+
+    test_root.delete();
+}